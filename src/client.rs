@@ -1,6 +1,7 @@
 use crate::json_request_processor::Reply;
 use crate::poll_data::{PollData, ReadData};
 use crate::Result;
+use std::collections::VecDeque;
 use std::fs::File;
 use std::io::{Read, Write};
 use std::net::Shutdown;
@@ -10,6 +11,7 @@ pub struct Client {
     server_stream: UnixStream,
     duplicate_request_write_target: Option<File>,
     json_bytes: Vec<u8>,
+    replies: VecDeque<Reply>,
 }
 
 pub enum ReplyState {
@@ -18,18 +20,37 @@ pub enum ReplyState {
 }
 
 impl<D: From<ReplyState>> ReadData<D> for Client {
+    /// The server can send notifications any time, so more than one reply
+    /// might be received at once. The ones after the first are kept, and
+    /// returned by the following calls, without reading from the server.
     fn read_data(&mut self) -> Result<D> {
+        if let Some(reply) = self.replies.pop_front() {
+            return Ok(ReplyState::Complete(reply).into());
+        }
         let mut buf = [0; 1024];
         let count = self.server_stream.read(&mut buf)?;
         self.json_bytes.extend_from_slice(&buf[0..count]);
-        match serde_json::from_slice::<Reply>(&self.json_bytes) {
-            Ok(reply) => {
-                self.json_bytes.clear();
-                Ok(ReplyState::Complete(reply).into())
+        let mut stream =
+            serde_json::Deserializer::from_slice(&self.json_bytes).into_iter::<Reply>();
+        let result = loop {
+            match stream.next() {
+                Some(Ok(reply)) => self.replies.push_back(reply),
+                Some(Err(err)) if !err.is_eof() => break Err(err),
+                Some(Err(_)) | None => break Ok(()),
             }
-            Err(err) if err.is_eof() => Ok(ReplyState::Incomplete.into()),
-            Err(err) => Err(err.into()),
+        };
+        let byte_offset = stream.byte_offset();
+        self.json_bytes.drain(0..byte_offset);
+        result?;
+        Ok(match self.replies.pop_front() {
+            Some(reply) => ReplyState::Complete(reply),
+            None => ReplyState::Incomplete,
         }
+        .into())
+    }
+
+    fn has_pending_data(&self) -> bool {
+        !self.replies.is_empty()
     }
 
     fn register(&self, poll_data: &mut PollData<D>) -> Result<()> {
@@ -44,6 +65,7 @@ impl Client {
             server_stream: server_socket,
             duplicate_request_write_target,
             json_bytes: vec![],
+            replies: VecDeque::new(),
         }
     }
 
@@ -62,3 +84,89 @@ impl Client {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::json_request_processor::Outcome;
+    use std::time::Duration;
+
+    const SCHEMA_NAME_REPLY: &str = r#"{"id":"1","outcome":{"schema_name":"朙月拼音"}}"#;
+    const NOTIFICATION_REPLY: &str = r#"{"id":null,"outcome":{"notification":{"message_type":"option","message_value":"ascii_mode"}}}"#;
+
+    fn connected_client() -> (Client, UnixStream) {
+        let (client_stream, server_stream) = UnixStream::pair().unwrap();
+        // Fail instead of hanging, if a test reads when there is nothing to.
+        client_stream
+            .set_read_timeout(Some(Duration::from_secs(1)))
+            .unwrap();
+        (Client::new(client_stream, None), server_stream)
+    }
+
+    fn read(client: &mut Client) -> ReplyState {
+        ReadData::<ReplyState>::read_data(client).unwrap()
+    }
+
+    fn has_pending_data(client: &Client) -> bool {
+        ReadData::<ReplyState>::has_pending_data(client)
+    }
+
+    fn is_schema_name_reply(reply_state: ReplyState) -> bool {
+        matches!(
+            reply_state,
+            ReplyState::Complete(Reply {
+                id: Some(id),
+                outcome: Outcome::SchemaName(schema_name),
+            }) if id == "1" && schema_name == "朙月拼音"
+        )
+    }
+
+    fn is_notification_reply(reply_state: ReplyState) -> bool {
+        matches!(
+            reply_state,
+            ReplyState::Complete(Reply {
+                id: None,
+                outcome: Outcome::Notification { message_type, message_value },
+            }) if message_type == "option" && message_value == "ascii_mode"
+        )
+    }
+
+    #[test]
+    fn two_replies_in_one_read() {
+        let (mut client, mut server_stream) = connected_client();
+        server_stream
+            .write_all((NOTIFICATION_REPLY.to_owned() + SCHEMA_NAME_REPLY).as_bytes())
+            .unwrap();
+        assert!(is_notification_reply(read(&mut client)));
+        assert!(has_pending_data(&client));
+        assert!(is_schema_name_reply(read(&mut client)));
+        assert!(!has_pending_data(&client));
+    }
+
+    #[test]
+    fn reply_split_across_reads() {
+        let (mut client, mut server_stream) = connected_client();
+        // In the middle of a multi-byte character.
+        let (first, second) = SCHEMA_NAME_REPLY.as_bytes().split_at(37);
+        server_stream.write_all(first).unwrap();
+        assert!(matches!(read(&mut client), ReplyState::Incomplete));
+        assert!(!has_pending_data(&client));
+        server_stream.write_all(second).unwrap();
+        assert!(is_schema_name_reply(read(&mut client)));
+        assert!(!has_pending_data(&client));
+    }
+
+    #[test]
+    fn complete_reply_then_partial_reply() {
+        let (mut client, mut server_stream) = connected_client();
+        let (first, second) = SCHEMA_NAME_REPLY.as_bytes().split_at(10);
+        server_stream
+            .write_all(&[NOTIFICATION_REPLY.as_bytes(), first].concat())
+            .unwrap();
+        assert!(is_notification_reply(read(&mut client)));
+        assert!(!has_pending_data(&client));
+        server_stream.write_all(second).unwrap();
+        assert!(is_schema_name_reply(read(&mut client)));
+        assert!(!has_pending_data(&client));
+    }
+}
@@ -6,8 +6,16 @@ pub enum Error {
     MoreThanOneClient,
     ServerClosedConnection,
     UnixSocketAlreadyExists,
+    UserDataDirectoryInUse(String),
+    ServerNotReady(std::path::PathBuf),
     ConfigNotFound(String),
     OptionNotFound(String),
+    SchemaNotFound(String),
+    CandidateNotFound(usize),
+    InvalidKeySequence(String),
+    ConfigNotSaved(String),
+    InvalidConfigValue(String),
+    InputNotSet(String),
     OneOfMultipleInputClosed,
     Io(std::io::Error),
     Json(serde_json::Error),
@@ -25,6 +33,7 @@ impl From<&Error> for std::process::ExitCode {
             UnsupportedInput => Self::from(3),
             UnixSocketAlreadyExists => Self::from(4),
             MoreThanOneClient => Self::from(5),
+            UserDataDirectoryInUse(_) => Self::from(6),
             _ => Self::FAILURE,
         }
     }
@@ -63,6 +72,28 @@ impl std::fmt::Debug for Error {
             Error::OptionNotFound(option_name) => {
                 write!(f, "the config option {} is not found", option_name,)
             }
+            Error::SchemaNotFound(schema_id) => {
+                write!(f, "the schema {} is not found", schema_id)
+            }
+            Error::CandidateNotFound(index) => {
+                write!(f, "the candidate at index {} is not found", index)
+            }
+            Error::InvalidKeySequence(key_sequence) => {
+                write!(f, "the key sequence {} is invalid", key_sequence)
+            }
+            Error::ConfigNotSaved(config_name) => {
+                write!(f, "changes to the config {} cannot be saved", config_name)
+            }
+            Error::InvalidConfigValue(option_name) => {
+                write!(
+                    f,
+                    "the value for the config option {} is invalid",
+                    option_name
+                )
+            }
+            Error::InputNotSet(input) => {
+                write!(f, "the input {} cannot be set", input)
+            }
             Error::NonUtf8DataHomePath => write!(
                 f,
                 "data directory path with non-UTF-8 characters is not supported"
@@ -80,6 +111,16 @@ impl std::fmt::Debug for Error {
             Error::MoreThanOneClient => {
                 write!(f, "there are other clients, so server cannot stop")
             }
+            Error::UserDataDirectoryInUse(unix_socket) => write!(
+                f,
+                "the user data directory is in use by the server on the unix socket {}",
+                unix_socket
+            ),
+            Error::ServerNotReady(log_file) => write!(
+                f,
+                "the server started did not get ready, see its log file {} for why",
+                log_file.display()
+            ),
             Error::Io(io_err) => io_err.fmt(f),
             Error::Json(json_err) => json_err.fmt(f),
             Error::NulInCString(nul_err) => nul_err.fmt(f),
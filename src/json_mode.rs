@@ -81,6 +81,16 @@ impl JsonMode {
                                 break;
                             }
                         }
+                        Reply {
+                            outcome: Outcome::Effects(ref effects),
+                            ..
+                        } if !continue_mode
+                            && effects
+                                .iter()
+                                .any(|effect| matches!(effect, Effect::CommitString(_))) =>
+                        {
+                            break;
+                        }
                         _ => (),
                     }
                 }
@@ -1,5 +1,7 @@
 use crate::key_processor::KeyProcessor;
-use crate::rime_api::RimeSession;
+use crate::rime_api::{
+    ConfigSource, ConfigValue, RimeCandidate, RimeSchema, RimeSession, RimeStatus, RimeSwitch,
+};
 use crate::{Call, Effect};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -29,9 +31,31 @@ pub struct Reply {
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub enum Outcome {
     Effect(Effect),
-    Error { id: ErrorId, message: String },
+    Effects(Vec<Effect>),
+    Error {
+        id: ErrorId,
+        message: String,
+    },
     SchemaName(String),
+    SchemaList(Vec<RimeSchema>),
+    Candidates(Vec<RimeCandidate>),
+    Switches(Vec<RimeSwitch>),
+    Status(RimeStatus),
+    OptionValue(bool),
+    Input(String),
+    CaretPos(usize),
     ConfigValueInteger(isize),
+    ConfigValue(ConfigValue),
+    ConfigDump(serde_json::Value),
+    /// Sent by the server on its own, with `id` being `None`, to the client
+    /// whose session the notification is about, or to every client for
+    /// `deploy`. `message_type` and `message_value` are what Rime notifies
+    /// of, for example `option` and `!ascii_mode` when ascii_mode is turned
+    /// off.
+    Notification {
+        message_type: String,
+        message_value: String,
+    },
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -43,6 +67,12 @@ pub enum ErrorId {
     IoError,
     OptionNotFound,
     ConfigNotFound,
+    SchemaNotFound,
+    CandidateNotFound,
+    InvalidKeySequence,
+    ConfigNotSaved,
+    InvalidConfigValue,
+    InputNotSet,
 }
 
 impl TryFrom<crate::Error> for Outcome {
@@ -67,6 +97,30 @@ impl TryFrom<crate::Error> for Outcome {
                 id: ErrorId::OptionNotFound,
                 message: format!("{:?}", err),
             }),
+            err @ SchemaNotFound(_) => Ok(Outcome::Error {
+                id: ErrorId::SchemaNotFound,
+                message: format!("{:?}", err),
+            }),
+            err @ CandidateNotFound(_) => Ok(Outcome::Error {
+                id: ErrorId::CandidateNotFound,
+                message: format!("{:?}", err),
+            }),
+            err @ InvalidKeySequence(_) => Ok(Outcome::Error {
+                id: ErrorId::InvalidKeySequence,
+                message: format!("{:?}", err),
+            }),
+            err @ ConfigNotSaved(_) => Ok(Outcome::Error {
+                id: ErrorId::ConfigNotSaved,
+                message: format!("{:?}", err),
+            }),
+            err @ InvalidConfigValue(_) => Ok(Outcome::Error {
+                id: ErrorId::InvalidConfigValue,
+                message: format!("{:?}", err),
+            }),
+            err @ InputNotSet(_) => Ok(Outcome::Error {
+                id: ErrorId::InputNotSet,
+                message: format!("{:?}", err),
+            }),
             Json(json_error) => Ok(Outcome::Error {
                 id: ErrorId::JsonError,
                 message: format!("{}", json_error),
@@ -89,15 +143,66 @@ impl JsonRequestProcessor<'_> {
     pub fn process_request(&self, Request { id, call: method }: Request) -> Reply {
         match method {
             Call::ConfigValueInteger {
+                source,
                 config_id,
                 option_key,
             } => Reply {
                 id: Some(id),
-                outcome: match self.rime_session.get_config_value(config_id, option_key) {
+                outcome: match self
+                    .rime_session
+                    .get_config_value(source, config_id, option_key)
+                {
                     Ok(value) => Outcome::ConfigValueInteger(value),
                     Err(err) => err.try_into().unwrap(),
                 },
             },
+            Call::ConfigValue {
+                source,
+                config_id,
+                key,
+                value_type,
+            } => Reply {
+                id: Some(id),
+                outcome: match self
+                    .rime_session
+                    .get_typed_config_value(source, config_id, key, value_type)
+                {
+                    Ok(value) => Outcome::ConfigValue(value),
+                    Err(err) => err.try_into().unwrap(),
+                },
+            },
+            Call::ConfigDump {
+                source,
+                config_id,
+                key,
+            } => Reply {
+                id: Some(id),
+                outcome: match self.rime_session.dump_config(source, config_id, key) {
+                    Ok(value) => Outcome::ConfigDump(value),
+                    Err(err) => err.try_into().unwrap(),
+                },
+            },
+            Call::ConfigSet {
+                config_id,
+                key,
+                value,
+            } => Reply {
+                id: Some(id),
+                outcome: match self
+                    .rime_session
+                    .customize_config(&config_id, &key, &value)
+                    .and_then(|()| {
+                        self.rime_session.get_typed_config_value(
+                            ConfigSource::Config,
+                            config_id,
+                            key,
+                            value.value_type(),
+                        )
+                    }) {
+                    Ok(value) => Outcome::ConfigValue(value),
+                    Err(err) => err.try_into().unwrap(),
+                },
+            },
             Call::SchemaName => {
                 let status = self.rime_session.get_status();
                 Reply {
@@ -105,14 +210,118 @@ impl JsonRequestProcessor<'_> {
                     outcome: Outcome::SchemaName(status.schema_name),
                 }
             }
+            Call::GetStatus => Reply {
+                id: Some(id),
+                outcome: Outcome::Status(self.rime_session.get_status()),
+            },
+            Call::GetOption { option } => Reply {
+                id: Some(id),
+                outcome: Outcome::OptionValue(self.rime_session.get_option(option)),
+            },
+            Call::SetOption { option, value } => Reply {
+                id: Some(id),
+                outcome: {
+                    self.rime_session.set_option(&option, value);
+                    Outcome::OptionValue(self.rime_session.get_option(&option))
+                },
+            },
+            Call::ListSchemas => Reply {
+                id: Some(id),
+                outcome: Outcome::SchemaList(self.rime_session.get_schema_list()),
+            },
+            Call::ListSwitches => Reply {
+                id: Some(id),
+                outcome: Outcome::Switches(self.rime_session.list_switches()),
+            },
+            Call::SelectSchema { schema_id } => Reply {
+                id: Some(id),
+                outcome: match self.rime_session.select_schema(schema_id) {
+                    Ok(()) => Outcome::Status(self.rime_session.get_status()),
+                    Err(err) => err.try_into().unwrap(),
+                },
+            },
             Call::ProcessKey { keycode, mask } => Reply {
                 id: Some(id),
-                outcome: Outcome::Effect(self.key_processor.process_key(
+                outcome: Outcome::Effects(self.key_processor.process_key(
                     self.rime_session,
                     keycode,
                     mask,
                 )),
             },
+            Call::SimulateKeySequence { sequence } => Reply {
+                id: Some(id),
+                outcome: match self
+                    .key_processor
+                    .simulate_key_sequence(self.rime_session, sequence)
+                {
+                    Ok(effects) => Outcome::Effects(effects),
+                    Err(err) => err.try_into().unwrap(),
+                },
+            },
+            Call::Candidates { offset, limit } => Reply {
+                id: Some(id),
+                outcome: Outcome::Candidates(self.rime_session.get_candidates(offset, limit)),
+            },
+            Call::SelectCandidate {
+                index,
+                on_current_page,
+            } => Reply {
+                id: Some(id),
+                outcome: match self.key_processor.select_candidate(
+                    self.rime_session,
+                    index,
+                    on_current_page,
+                ) {
+                    Ok(effects) => Outcome::Effects(effects),
+                    Err(err) => err.try_into().unwrap(),
+                },
+            },
+            Call::DeleteCandidate {
+                index,
+                on_current_page,
+            } => Reply {
+                id: Some(id),
+                outcome: match self.key_processor.delete_candidate(
+                    self.rime_session,
+                    index,
+                    on_current_page,
+                ) {
+                    Ok(effects) => Outcome::Effects(effects),
+                    Err(err) => err.try_into().unwrap(),
+                },
+            },
+            Call::ChangePage { backward } => Reply {
+                id: Some(id),
+                outcome: {
+                    // Rime refuses to turn the page beyond the first or the last one,
+                    // in which case the menu simply stays the same.
+                    self.rime_session.change_page(backward);
+                    Outcome::Effect(self.key_processor.update_ui(self.rime_session))
+                },
+            },
+            Call::GetInput => Reply {
+                id: Some(id),
+                outcome: Outcome::Input(self.rime_session.get_input()),
+            },
+            Call::SetInput { text } => Reply {
+                id: Some(id),
+                outcome: if self.rime_session.set_input(&text) {
+                    Outcome::Effect(self.key_processor.update_ui(self.rime_session))
+                } else {
+                    crate::Error::InputNotSet(text).try_into().unwrap()
+                },
+            },
+            Call::GetCaretPos => Reply {
+                id: Some(id),
+                outcome: Outcome::CaretPos(self.rime_session.get_caret_pos()),
+            },
+            Call::SetCaretPos { pos } => Reply {
+                id: Some(id),
+                outcome: {
+                    self.rime_session.set_caret_pos(pos);
+                    Outcome::Effect(self.key_processor.update_ui(self.rime_session))
+                },
+            },
             Call::StopClient => Reply {
                 id: Some(id),
                 outcome: Outcome::Effect(Effect::StopClient),
@@ -121,15 +330,15 @@ impl JsonRequestProcessor<'_> {
                 id: Some(id),
                 outcome: Outcome::Effect(Effect::StopServer),
             },
+            Call::CommitComposition => Reply {
+                id: Some(id),
+                outcome: Outcome::Effect(self.key_processor.commit_composition(self.rime_session)),
+            },
             Call::ClearComposition => Reply {
                 id: Some(id),
                 outcome: {
                     self.rime_session.clear_composition();
-                    let context = self.rime_session.get_context();
-                    Outcome::Effect(Effect::UpdateUi {
-                        composition: context.composition,
-                        menu: context.menu,
-                    })
+                    Outcome::Effect(self.key_processor.update_ui(self.rime_session))
                 },
             },
         }
@@ -159,7 +368,97 @@ mod test {
         );
         assert_eq!(
             serde_json::to_string(&schema_reply).unwrap(),
-            r#"{"id":"22","outcome":{"schema_name":"luna_pinyin"}}"#
+            r#"{"id":"22","outcome":{"schema_name":"朙月拼音"}}"#
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn get_status() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            crate::testing_utilities::temporary_directory_path(),
+            "./test_shared_data",
+            crate::testing_utilities::LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let json_request_processor = JsonRequestProcessor {
+            key_processor: KeyProcessor::new(),
+            rime_session: &rime_session,
+        };
+        let status_reply = json_request_processor.process_request(
+            serde_json::from_str(r#"{"id":"22","call":{"method":"get_status"}}"#).unwrap(),
+        );
+        assert_eq!(
+            serde_json::to_string(&status_reply).unwrap(),
+            r#"{"id":"22","outcome":{"status":{"schema_id":"luna_pinyin","schema_name":"朙月拼音","is_disabled":false,"is_composing":false,"is_ascii_mode":false,"is_full_shape":false,"is_simplified":false,"is_traditional":false,"is_ascii_punct":false}}}"#
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn select_unknown_schema() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            crate::testing_utilities::temporary_directory_path(),
+            "./test_shared_data",
+            crate::testing_utilities::LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let json_request_processor = JsonRequestProcessor {
+            key_processor: KeyProcessor::new(),
+            rime_session: &rime_session,
+        };
+        let reply = json_request_processor.process_request(
+            serde_json::from_str(
+                r#"{
+                    "id": "22",
+                    "call": {
+                        "method": "select_schema",
+                        "params": {
+                            "schema_id": "no_such_schema"
+                        }
+                    }
+                }"#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"id":"22","outcome":{"error":{"id":"schema_not_found","message":"the schema no_such_schema is not found"}}}"#
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn config_value() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            crate::testing_utilities::temporary_directory_path(),
+            "./test_shared_data",
+            crate::testing_utilities::LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let json_request_processor = JsonRequestProcessor {
+            key_processor: KeyProcessor::new(),
+            rime_session: &rime_session,
+        };
+        let reply = json_request_processor.process_request(
+            serde_json::from_str(
+                r#"{
+                    "id": "22",
+                    "call": {
+                        "method": "config_value",
+                        "params": {
+                            "config_id": "default",
+                            "key": "switcher/option_list_separator",
+                            "type": "string"
+                        }
+                    }
+                }"#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            serde_json::to_string(&reply).unwrap(),
+            r#"{"id":"22","outcome":{"config_value":{"string":"／"}}}"#
         );
     }
 
@@ -194,7 +493,7 @@ mod test {
         );
         assert_eq!(
             serde_json::to_string(&schema_reply).unwrap(),
-            r#"{"id":"22","outcome":{"effect":{"update_ui":{"composition":{"length":18,"cursor_pos":0,"sel_start":0,"sel_end":0,"preedit":"〔方案選單〕"},"menu":{"candidates":[{"text":"朙月拼音","comment":null},{"text":"中／半／漢／。","comment":null},{"text":"朙月拼音·简化字","comment":null},{"text":"朙月拼音·語句流","comment":null},{"text":"bopomofo","comment":null}],"page_no":0,"highlighted_candidate_index":0,"is_last_page":false}}}}}"#
+            r#"{"id":"22","outcome":{"effects":[{"update_ui":{"composition":{"length":18,"cursor_pos":0,"sel_start":0,"sel_end":0,"preedit":"〔方案選單〕","char_positions":{"cursor_pos":0,"sel_start":0,"sel_end":0},"utf16_positions":{"cursor_pos":0,"sel_start":0,"sel_end":0}},"menu":{"candidates":[{"text":"朙月拼音","comment":null},{"text":"中／半／漢／。","comment":null},{"text":"朙月拼音·简化字","comment":null},{"text":"朙月拼音·語句流","comment":null},{"text":"bopomofo","comment":null}],"page_no":0,"highlighted_candidate_index":0,"is_last_page":false,"page_size":5,"select_keys":null,"labels":["1","2","3","4","5"]},"commit_text_preview":"朙月拼音","status":{"schema_id":"luna_pinyin","is_ascii_mode":false,"is_full_shape":false}}}]}}"#
         );
     }
 }
@@ -1,5 +1,6 @@
-use crate::rime_api::RimeSession;
+use crate::rime_api::{RimeSession, RimeStatus};
 use crate::Effect;
+use crate::{Error, Result};
 
 pub struct KeyProcessor;
 
@@ -8,22 +9,107 @@ impl KeyProcessor {
         Self
     }
 
-    pub fn process_key(&self, rime_session: &RimeSession, keycode: usize, mask: usize) -> Effect {
-        let key_processed = rime_session.process_key(keycode, mask);
-        match (key_processed, rime_session.get_commit().text) {
-            (true, Some(commit_string)) => Effect::CommitString(commit_string),
-            (false, commit_string) => Effect::RawKeyEvent {
+    /// Keys that Rime does not process are reported as one
+    /// `Effect::RawKeyEvent`. Otherwise, see `effects_since`.
+    pub fn process_key(
+        &self,
+        rime_session: &RimeSession,
+        keycode: usize,
+        mask: usize,
+    ) -> Vec<Effect> {
+        let status = rime_session.get_status();
+        if !rime_session.process_key(keycode, mask) {
+            return vec![Effect::RawKeyEvent {
                 keycode,
                 mask,
-                accompanying_commit_string: commit_string,
-            },
-            _ => {
-                let context = rime_session.get_context();
-                Effect::UpdateUi {
-                    composition: context.composition,
-                    menu: context.menu,
-                }
-            }
+                accompanying_commit_string: rime_session.get_commit().text,
+            }];
+        }
+        self.effects_since(rime_session, status)
+    }
+
+    /// Unlike `process_key`, keys that Rime does not process are dropped,
+    /// and all the text committed during the sequence is reported as one
+    /// `Effect::CommitString`.
+    pub fn simulate_key_sequence(
+        &self,
+        rime_session: &RimeSession,
+        key_sequence: String,
+    ) -> Result<Vec<Effect>> {
+        let status = rime_session.get_status();
+        if !rime_session.simulate_key_sequence(&key_sequence) {
+            return Err(Error::InvalidKeySequence(key_sequence));
+        }
+        Ok(self.effects_since(rime_session, status))
+    }
+
+    pub fn select_candidate(
+        &self,
+        rime_session: &RimeSession,
+        index: usize,
+        on_current_page: bool,
+    ) -> Result<Vec<Effect>> {
+        let status = rime_session.get_status();
+        if !rime_session.select_candidate(index, on_current_page) {
+            return Err(Error::CandidateNotFound(index));
+        }
+        Ok(self.effects_since(rime_session, status))
+    }
+
+    /// Replies the same way as `select_candidate`, so that clients handle
+    /// the candidate calls alike.
+    pub fn delete_candidate(
+        &self,
+        rime_session: &RimeSession,
+        index: usize,
+        on_current_page: bool,
+    ) -> Result<Vec<Effect>> {
+        let status = rime_session.get_status();
+        if !rime_session.delete_candidate(index, on_current_page) {
+            return Err(Error::CandidateNotFound(index));
+        }
+        Ok(self.effects_since(rime_session, status))
+    }
+
+    /// The effects of the input Rime has processed since `status` was taken,
+    /// in order: an `Effect::CommitString` if anything was committed, an
+    /// `Effect::UpdateUi` showing the state after the input, and then an
+    /// `Effect::OptionChanged` for each option that changed.
+    fn effects_since(&self, rime_session: &RimeSession, status: RimeStatus) -> Vec<Effect> {
+        let commit = rime_session.get_commit().text.map(Effect::CommitString);
+        let update_ui = self.update_ui(rime_session);
+        let option_changes = status
+            .options()
+            .into_iter()
+            .zip(rime_session.get_status().options())
+            .filter(|((_, before), (_, after))| before != after)
+            .map(|(_, (option, value))| Effect::OptionChanged {
+                option: option.into(),
+                value,
+            });
+        commit
+            .into_iter()
+            .chain(std::iter::once(update_ui))
+            .chain(option_changes)
+            .collect()
+    }
+
+    /// When there is nothing to commit, the UI is updated instead.
+    pub fn commit_composition(&self, rime_session: &RimeSession) -> Effect {
+        let committed = rime_session.commit_composition();
+        match (committed, rime_session.get_commit().text) {
+            (true, Some(commit_string)) => Effect::CommitString(commit_string),
+            _ => self.update_ui(rime_session),
+        }
+    }
+
+    pub fn update_ui(&self, rime_session: &RimeSession) -> Effect {
+        let context = rime_session.get_context();
+        Effect::UpdateUi {
+            composition: context.composition,
+            menu: context.menu.into(),
+            commit_text_preview: context.commit_text_preview,
+            status: rime_session.get_status().into(),
         }
     }
 }
@@ -44,49 +130,172 @@ mod test {
         );
         let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
         let key_processor = KeyProcessor::new();
-        let report = key_processor.process_key(&rime_session, 109 /* m */, 0);
+        let effects = key_processor.process_key(&rime_session, 109 /* m */, 0);
         assert_eq!(
-            match report {
-                Effect::UpdateUi { composition, menu } =>
-                    (composition.preedit, menu.candidates.len()),
+            match &effects[..] {
+                [Effect::UpdateUi {
+                    composition, menu, ..
+                }] => (composition.preedit.as_str(), menu.candidates.len()),
                 _ => panic!(),
             },
-            ("m".into(), 5),
+            ("m", 5),
         );
-        let report = key_processor.process_key(&rime_session, 73 /* I */, 0);
+        let effects = key_processor.process_key(&rime_session, 73 /* I */, 0);
         assert_eq!(
-            match report {
-                Effect::UpdateUi { composition, menu } =>
-                    (composition.preedit, menu.candidates.len()),
+            match &effects[..] {
+                [Effect::UpdateUi {
+                    composition, menu, ..
+                }] => (composition.preedit.as_str(), menu.candidates.len()),
                 _ => panic!(),
             },
-            ("骂I".into(), 0),
+            ("骂I", 0),
         );
-        let response = key_processor.process_key(&rime_session, 78 /* N */, 0);
+        let effects = key_processor.process_key(&rime_session, 78 /* N */, 0);
         assert_eq!(
-            match response {
-                Effect::UpdateUi { composition, menu } =>
-                    (composition.preedit, menu.candidates.len()),
+            match &effects[..] {
+                [Effect::UpdateUi {
+                    composition, menu, ..
+                }] => (composition.preedit.as_str(), menu.candidates.len()),
                 _ => panic!(),
             },
-            ("骂IN".into(), 0),
+            ("骂IN", 0),
         );
-        let report = key_processor.process_key(&rime_session, 89 /* Y */, 0);
+        let effects = key_processor.process_key(&rime_session, 89 /* Y */, 0);
         assert_eq!(
-            match report {
-                Effect::UpdateUi { composition, menu } =>
-                    (composition.preedit, menu.candidates.len()),
+            match &effects[..] {
+                [Effect::UpdateUi {
+                    composition, menu, ..
+                }] => (composition.preedit.as_str(), menu.candidates.len()),
                 _ => panic!(),
             },
-            ("骂INY".into(), 0),
+            ("骂INY", 0),
         );
-        let report = key_processor.process_key(&rime_session, 32 /* space */, 0);
+        let effects = key_processor.process_key(&rime_session, 32 /* space */, 0);
+        assert_eq!(
+            match &effects[..] {
+                [Effect::CommitString(commit_string), Effect::UpdateUi { composition, .. }] =>
+                    (commit_string.as_str(), composition.preedit.as_str()),
+                _ => panic!(),
+            },
+            ("骂INY", ""),
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn option_changed() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let key_processor = KeyProcessor::new();
+        // Shift switches ascii_mode when it is released.
+        key_processor.process_key(&rime_session, 0xffe1 /* Shift_L */, 0);
+        let effects = key_processor.process_key(
+            &rime_session,
+            0xffe1,  /* Shift_L */
+            1 << 30, /* release */
+        );
+        assert_eq!(
+            match &effects[..] {
+                [Effect::UpdateUi { .. }, Effect::OptionChanged { option, value }] =>
+                    (option.as_str(), *value),
+                _ => panic!(),
+            },
+            ("ascii_mode", true),
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn simulate_key_sequence() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let key_processor = KeyProcessor::new();
+        assert!(key_processor
+            .simulate_key_sequence(&rime_session, "{NoSuchKey}".into())
+            .is_err());
+        let effects = key_processor
+            .simulate_key_sequence(&rime_session, "mno{Return}mi".into())
+            .unwrap();
+        assert_eq!(effects.len(), 2);
+        assert_eq!(
+            match &effects[0] {
+                Effect::CommitString(commit_string) => commit_string.as_str(),
+                _ => panic!(),
+            },
+            "mno",
+        );
+        assert_eq!(
+            match &effects[1] {
+                Effect::UpdateUi { composition, .. } => composition.preedit.as_str(),
+                _ => panic!(),
+            },
+            "mi",
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn commit_composition() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let key_processor = KeyProcessor::new();
+        key_processor.process_key(&rime_session, 109 /* m */, 0);
+        let report = key_processor.commit_composition(&rime_session);
         assert_eq!(
             match report {
                 Effect::CommitString(commit_string) => commit_string,
                 _ => panic!(),
             },
-            "骂INY",
+            "骂",
+        );
+        let report = key_processor.commit_composition(&rime_session);
+        assert_eq!(
+            match report {
+                Effect::UpdateUi {
+                    composition, menu, ..
+                } => (composition.preedit, menu.candidates.len()),
+                _ => panic!(),
+            },
+            ("".into(), 0),
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn select_candidate() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let key_processor = KeyProcessor::new();
+        key_processor.process_key(&rime_session, 109 /* m */, 0);
+        assert!(key_processor
+            .select_candidate(&rime_session, 10, true)
+            .is_err());
+        let effects = key_processor
+            .select_candidate(&rime_session, 0, true)
+            .unwrap();
+        assert_eq!(
+            match &effects[..] {
+                [Effect::CommitString(commit_string), Effect::UpdateUi { .. }] =>
+                    commit_string.as_str(),
+                _ => panic!(),
+            },
+            "骂",
         );
     }
 }
@@ -0,0 +1,138 @@
+//! Logging for the server, which runs in the background without a terminal
+//! to report problems on. Logs are appended to a file.
+
+use crate::Result;
+use serde::Serialize;
+use std::fs::{create_dir_all, File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// From the most severe to the least. Each level also enables the levels
+/// before it.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    None,
+    Error,
+    Warning,
+    Info,
+    /// Also log every request received and every reply sent.
+    Trace,
+}
+
+impl LogLevel {
+    fn name(self) -> &'static str {
+        match self {
+            LogLevel::None => "NONE",
+            LogLevel::Error => "ERROR",
+            LogLevel::Warning => "WARNING",
+            LogLevel::Info => "INFO",
+            LogLevel::Trace => "TRACE",
+        }
+    }
+}
+
+struct Logger {
+    level: LogLevel,
+    file: Mutex<File>,
+}
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Open the log file at `path` for appending, creating it and the directories
+/// leading to it if necessary.
+pub fn open_log_file(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent)?;
+    }
+    Ok(OpenOptions::new().create(true).append(true).open(path)?)
+}
+
+/// Start logging to the file at `path`. Before this is called, nothing is
+/// logged. Only the first call has an effect.
+pub fn init(path: &Path, level: LogLevel) -> Result<()> {
+    let file = open_log_file(path)?;
+    let _ = LOGGER.set(Logger {
+        level,
+        file: Mutex::new(file),
+    });
+    Ok(())
+}
+
+pub fn enabled(level: LogLevel) -> bool {
+    LOGGER
+        .get()
+        .is_some_and(|logger| level != LogLevel::None && level <= logger.level)
+}
+
+/// Use `log!` instead, which skips formatting when `level` is not enabled.
+pub fn write(level: LogLevel, message: std::fmt::Arguments) {
+    let Some(logger) = LOGGER.get() else {
+        return;
+    };
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    // There is nowhere else to report a failure to log.
+    let _ = writeln!(
+        logger.file.lock().unwrap(),
+        "{} {} {}",
+        format_timestamp(seconds),
+        level.name(),
+        message
+    );
+}
+
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        if $crate::logging::enabled($level) {
+            $crate::logging::write($level, format_args!($($arg)*))
+        }
+    };
+}
+pub(crate) use log;
+
+/// Format seconds since the Unix epoch as an RFC 3339 timestamp in UTC.
+fn format_timestamp(seconds: u64) -> String {
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+    // The civil_from_days algorithm by Howard Hinnant, with March as the
+    // first month of the year, so that the leap day comes last.
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day / 60 % 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timestamp() {
+        assert_eq!("1970-01-01T00:00:00Z", format_timestamp(0));
+        assert_eq!("2000-02-29T00:00:00Z", format_timestamp(951782400));
+        assert_eq!("2026-10-17T11:24:05Z", format_timestamp(1792236245));
+    }
+}
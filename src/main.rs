@@ -6,6 +6,7 @@ mod error;
 mod json_mode;
 mod json_request_processor;
 mod key_processor;
+mod logging;
 mod poll_data;
 mod rime_api;
 mod server_mode;
@@ -13,20 +14,21 @@ mod terminal_interface;
 mod terminal_json_mode;
 mod terminal_mode;
 use crate::client::Client;
-use crate::server_mode::ServerMode;
-use clap::Parser;
+use crate::server_mode::{lock_user_data_directory, ServerMode};
+use clap::{Parser, ValueEnum};
 use error::Error;
 use json_mode::JsonMode;
 use json_request_processor::Request;
-use rime_api::{RimeComposition, RimeMenu};
+use rime_api::{RimeComposition, RimeMenu, RimeUiStatus};
 use schemars::schema_for;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::fs::remove_file;
-use std::io::{stdout, ErrorKind, Write};
+use std::fs::{remove_file, File};
+use std::io::{stdout, ErrorKind, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, RawFd};
 use std::os::unix::net::UnixListener;
 use std::os::unix::net::UnixStream;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Command, ExitCode, Stdio};
 use terminal_json_mode::TerminalJsonMode;
 use terminal_mode::TerminalMode;
@@ -45,15 +47,82 @@ pub enum Call {
     StopClient,
     StopServer,
     SchemaName,
+    GetStatus,
+    GetOption {
+        option: String,
+    },
+    SetOption {
+        option: String,
+        value: bool,
+    },
+    ListSchemas,
+    /// List the switches of the current schema, with the current values of
+    /// their options.
+    ListSwitches,
+    SelectSchema {
+        schema_id: String,
+    },
     ClearComposition,
+    CommitComposition,
     ConfigValueInteger {
+        #[serde(default)]
+        source: rime_api::ConfigSource,
         config_id: String,
         option_key: String,
     },
+    ConfigValue {
+        #[serde(default)]
+        source: rime_api::ConfigSource,
+        config_id: String,
+        key: String,
+        #[serde(rename = "type")]
+        value_type: rime_api::ConfigValueType,
+    },
+    ConfigDump {
+        #[serde(default)]
+        source: rime_api::ConfigSource,
+        config_id: String,
+        key: String,
+    },
+    /// Persist a config value as a patch in `<config_id>.custom.yaml` in the
+    /// user data directory, and deploy it.
+    ConfigSet {
+        config_id: String,
+        key: String,
+        value: rime_api::ConfigValue,
+    },
     ProcessKey {
         keycode: usize,
         mask: usize,
     },
+    SimulateKeySequence {
+        sequence: String,
+    },
+    /// Get at most `limit` candidates, starting from the one at `offset`,
+    /// from all the candidates rather than just the current page.
+    Candidates {
+        offset: usize,
+        limit: usize,
+    },
+    SelectCandidate {
+        index: usize,
+        on_current_page: bool,
+    },
+    DeleteCandidate {
+        index: usize,
+        on_current_page: bool,
+    },
+    ChangePage {
+        backward: bool,
+    },
+    GetInput,
+    SetInput {
+        text: String,
+    },
+    GetCaretPos,
+    SetCaretPos {
+        pos: usize,
+    },
 }
 
 #[derive(Clone, Serialize, Deserialize, JsonSchema)]
@@ -70,7 +139,13 @@ pub enum Effect {
     },
     UpdateUi {
         composition: RimeComposition,
-        menu: RimeMenu,
+        menu: Box<RimeMenu>,
+        commit_text_preview: Option<String>,
+        status: RimeUiStatus,
+    },
+    OptionChanged {
+        option: String,
+        value: bool,
     },
 }
 
@@ -88,18 +163,23 @@ pub struct Args {
     /// Used for debugging.
     #[arg(long = "duplicate-requests")]
     duplicate_requests: Option<PathBuf>,
-    /// A path to the file where the server's stderr gets redirected to.
-    ///
-    /// Used for debugging.
-    #[arg(long = "redirect-server-stderr", requires = "force_start_server")]
-    redirect_server_stderr: Option<PathBuf>,
     #[arg(long, value_enum, default_value = "none")]
     /// The lowest level of Rime logs to write to stderr.
     ///
-    /// When `none`, no logs will be written.
+    /// When `none`, no logs will be written. The stderr of a server started
+    /// by a client is the log file of the server.
     ///
     /// Note that this controls the logs generated by the Rime engine, not rimecmd.
     rime_log_level: rime_api::LogLevel,
+    #[arg(long, value_enum, default_value = "warning")]
+    /// The lowest level of the server's logs to write to its log file.
+    ///
+    /// `trace` also logs every request and reply. The log file can be seen
+    /// using `--print-config`.
+    ///
+    /// Like `--user-data-directory`, this only affects a server being started
+    /// by the command invocation with this argument.
+    log_level: logging::LogLevel,
     #[arg(long)]
     /// Use JSON for input/output.
     ///
@@ -142,7 +222,10 @@ pub struct Args {
     /// Normally, a user won't need to use this flag. When a client runs, if it can't
     /// find a server to connect to, it automatically starts the server.
     ///
-    /// All other arguments, except for `unix_socket` are ignored when this is used.
+    /// All other arguments, except for `unix_socket`, `user_data_directory`,
+    /// `idle_timeout`, `force_start_server` and the log levels, are ignored
+    /// when this is used.
+    // So is the hidden `ready_fd`, which only makes sense with this.
     server: bool,
     /// Print the configuration used by `rimecmd` and exit.
     ///
@@ -157,14 +240,35 @@ pub struct Args {
     ///
     /// Normally, the server creates the unix socket it uses. However, it is
     /// possible that when it tries to create the unix socket, it finds that
-    /// a file already exists at the path. This normally means that another
-    /// instance of server is running. However, other things might also
-    /// cause this to happen, such as that a server has crashed. If you can
-    /// be sure that there is not another server instance running, and that
-    /// the file at the path does not contain information you want to keep,
-    /// use this flag. It will remove the file if it exists and then start
-    /// the server as normal.
+    /// a file already exists at the path. If no server listens on it, for
+    /// example because the server that created it has crashed, the file is
+    /// removed automatically, and this flag is not needed. Otherwise, this
+    /// means that another instance of server is running, and this flag
+    /// removes the file anyway, and then starts the server as normal. The
+    /// server already running can then no longer be connected to. This
+    /// does not apply to a server using the same user data directory, which
+    /// keeps running, while the new server fails to start.
     force_start_server: bool,
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    /// How long to wait for a server started by this command invocation to
+    /// get ready.
+    ///
+    /// This does not include the time Rime takes to deploy, which happens
+    /// after the server is ready to accept connections.
+    server_start_timeout: u64,
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    /// Stop the server after it has had no clients for this long.
+    ///
+    /// When absent, the server keeps running until it is stopped. This only
+    /// affects a server being started by the command invocation with this
+    /// argument. At least 1, so that the server does not stop before the
+    /// client that starts it connects.
+    idle_timeout: Option<u64>,
+    /// The file descriptor of a pipe to write to once the server is ready.
+    ///
+    /// Used by a client to wait for the server it starts.
+    #[arg(long, hide = true, requires = "server")]
+    ready_fd: Option<RawFd>,
     /// Stop the server and exit.
     #[arg(long, exclusive = true)]
     stop_server: bool,
@@ -189,6 +293,10 @@ pub struct Config {
     pub unix_socket: PathBuf,
     pub user_data_directory: PathBuf,
     pub rime_log_level: rime_api::LogLevel,
+    pub log_file: PathBuf,
+    pub log_level: logging::LogLevel,
+    pub server_start_timeout: u64,
+    pub idle_timeout: Option<u64>,
 }
 
 impl TryFrom<&Args> for Config {
@@ -207,14 +315,15 @@ impl TryFrom<&Args> for Config {
         };
         Ok(Self {
             unix_socket,
-            user_data_directory: if args.server || args.force_start_server {
-                args.user_data_directory.clone()
-            } else {
-                None
-            }
-            .or(Some(xdg_directories.get_data_home().into()))
-            .unwrap(),
+            user_data_directory: args
+                .user_data_directory
+                .clone()
+                .unwrap_or_else(|| xdg_directories.get_data_home()),
             rime_log_level: args.rime_log_level,
+            log_file: xdg_directories.get_state_home().join("server.log"),
+            log_level: args.log_level,
+            server_start_timeout: args.server_start_timeout,
+            idle_timeout: args.idle_timeout,
         })
     }
 }
@@ -233,6 +342,7 @@ fn print_error_codes() -> Result<()> {
         UnsupportedInput,
         UnixSocketAlreadyExists,
         MoreThanOneClient,
+        UserDataDirectoryInUse("<unix socket>".into()),
     ] {
         println!("{:?} {:?}", ExitCode::from(&error), error);
     }
@@ -260,22 +370,101 @@ fn print_json_schema(json_schema: PrintJsonSchemaFor) -> Result<()> {
     Ok(())
 }
 
-fn start_server(log_path: Option<PathBuf>) -> Result<()> {
-    let log_path = match log_path {
-        Some(path) => std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(path)?
-            .into(),
-        None => Stdio::null(),
-    };
-    Command::new(std::env::args().nth(0).unwrap())
+/// Start a server in the background, and wait until it is ready.
+///
+/// * `force` - Whether the server replaces the unix socket even if another
+///   server listens on it. See `--force-start-server`.
+fn start_server(config: &Config, force: bool) -> Result<()> {
+    // Anything the server cannot log itself, such as a panic, still
+    // ends up in the log file this way.
+    let log_file = logging::open_log_file(&config.log_file)?;
+    let mut fds = [0; 2];
+    if -1 == unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let (mut ready_reader, ready_writer) =
+        unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    // Only the writing end is inherited by the server.
+    if -1 == unsafe { libc::fcntl(ready_writer.as_raw_fd(), libc::F_SETFD, 0) } {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    let mut command = Command::new(std::env::args().nth(0).unwrap());
+    if let Some(idle_timeout) = config.idle_timeout {
+        command.arg("--idle-timeout").arg(idle_timeout.to_string());
+    }
+    if force {
+        command.arg("--force-start-server");
+    }
+    command
         .arg("--server")
+        .arg("--unix-socket")
+        .arg(&config.unix_socket)
+        .arg("--user-data-directory")
+        .arg(&config.user_data_directory)
+        .arg("--log-level")
+        .arg(config.log_level.to_possible_value().unwrap().get_name())
+        .arg("--rime-log-level")
+        .arg(
+            config
+                .rime_log_level
+                .to_possible_value()
+                .unwrap()
+                .get_name(),
+        )
+        .arg("--ready-fd")
+        .arg(ready_writer.as_raw_fd().to_string())
         .stdin(Stdio::null())
         .stdout(Stdio::null())
-        .stderr(log_path)
+        .stderr(log_file)
         .spawn()?;
-    Ok(())
+    // Otherwise, the pipe is not closed when the server exits.
+    drop(ready_writer);
+    let mut poll_fd = libc::pollfd {
+        fd: ready_reader.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = config
+        .server_start_timeout
+        .saturating_mul(1000)
+        .try_into()
+        .unwrap_or(libc::c_int::MAX);
+    match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+        -1 => Err(std::io::Error::last_os_error().into()),
+        // Timed out.
+        0 => Err(Error::ServerNotReady(config.log_file.clone())),
+        // Nothing is read when the server exits without writing.
+        _ => match ready_reader.read(&mut [0])? {
+            0 => Err(Error::ServerNotReady(config.log_file.clone())),
+            _ => Ok(()),
+        },
+    }
+}
+
+/// When there is already a file at `path`, it is removed if nobody listens
+/// on it, which means it is left behind by a server that did not exit
+/// normally, or if `force` is true.
+///
+/// Only to be called while holding the lock on the user data directory.
+fn bind_unix_socket(path: &Path, force: bool) -> Result<UnixListener> {
+    match UnixListener::bind(path) {
+        Ok(unix_listener) => Ok(unix_listener),
+        Err(error) if error.kind() == ErrorKind::AddrInUse => {
+            if !force {
+                // A server listening, which uses another user data directory,
+                // sees this as a client that connects and leaves without a
+                // request, and logs the connection as opened and closed.
+                match UnixStream::connect(path) {
+                    Ok(_) => return Err(Error::UnixSocketAlreadyExists),
+                    Err(error) if error.kind() == ErrorKind::ConnectionRefused => (),
+                    Err(error) => return Err(error.into()),
+                }
+            }
+            remove_file(path)?;
+            Ok(UnixListener::bind(path)?)
+        }
+        Err(error) => Err(error.into()),
+    }
 }
 
 fn rimecmd() -> Result<()> {
@@ -291,36 +480,34 @@ fn rimecmd() -> Result<()> {
         return print_error_codes();
     }
     if args.server {
-        let unix_listener = match UnixListener::bind(&config.unix_socket) {
-            Ok(unix_listener) => unix_listener,
-            Err(error) => match error.kind() {
-                ErrorKind::AddrInUse if args.force_start_server => {
-                    UnixListener::bind(&config.unix_socket)?
-                }
-                ErrorKind::AddrInUse => {
-                    return Err(Error::UnixSocketAlreadyExists);
-                }
-                _ => return Err(error.into()),
-            },
-        };
-        return ServerMode::new(config, unix_listener).main();
+        // Taken first, so that the pipe is closed even if the server
+        // fails to start.
+        let ready_pipe = args.ready_fd.map(|fd| unsafe { File::from_raw_fd(fd) });
+        // Locked before the unix socket is touched, so that a server that
+        // cannot use the user data directory leaves alone the socket of the
+        // server that does.
+        let user_data_directory_lock = lock_user_data_directory(&config)?;
+        let unix_listener = bind_unix_socket(&config.unix_socket, args.force_start_server)?;
+        return ServerMode::new(config, user_data_directory_lock, unix_listener, ready_pipe).main();
     } else if args.force_start_server {
-        remove_file(&config.unix_socket).unwrap_or(());
-        return start_server(args.redirect_server_stderr);
+        return start_server(&config, true);
     }
     let server_stream = match UnixStream::connect(&config.unix_socket) {
         Ok(server_stream) => server_stream,
         Err(error) => match error.kind() {
-            ErrorKind::NotFound => {
-                start_server(args.redirect_server_stderr)?;
-                loop {
-                    match UnixStream::connect(&config.unix_socket) {
-                        Ok(server_stream) => break server_stream,
-                        Err(error) => match error.kind() {
-                            ErrorKind::NotFound => continue,
-                            _ => return Err(error.into()),
-                        },
+            // When nobody listens on the socket, it is left behind by a
+            // server that did not exit normally. The server started replaces
+            // it, once it has the user data directory to itself.
+            ErrorKind::NotFound | ErrorKind::ConnectionRefused => {
+                match start_server(&config, false) {
+                    Ok(()) => UnixStream::connect(&config.unix_socket)?,
+                    // When another client starts a server at the same time,
+                    // only one of the servers gets the socket, and the other
+                    // exits without getting ready.
+                    Err(error @ Error::ServerNotReady(_)) => {
+                        UnixStream::connect(&config.unix_socket).map_err(|_| error)?
                     }
+                    Err(error) => return Err(error),
                 }
             }
             _ => return Err(error.into()),
@@ -376,15 +563,12 @@ fn main() -> ExitCode {
         Err(error @ Error::UnixSocketAlreadyExists) => {
             eprintln!(
                 "When the server tries to create a unix socket to listen to, \
-                it finds that there already exists one."
-            );
-            eprintln!("This usually means that a server is already running.");
-            eprintln!(
-                "If you are sure that there does not exist \
-                an already running server instance, use `--force-start-server`."
+                it finds that another server is already listening on it."
             );
+            eprintln!("Use `--stop-server` to stop the running server first.");
             eprintln!(
-                "This will remove the already existing unix socket and then start the server."
+                "`--force-start-server` starts a new server anyway, \
+                but the running one can no longer be connected to after that."
             );
             error.into()
         }
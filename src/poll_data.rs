@@ -7,6 +7,11 @@ use std::rc::Rc;
 pub trait ReadData<D> {
     fn read_data(&mut self) -> Result<D>;
     fn register(&self, poll_data: &mut PollData<D>) -> Result<()>;
+    /// Whether `read_data` can return without reading from the file
+    /// registered, which is then not going to be reported ready by epoll.
+    fn has_pending_data(&self) -> bool {
+        false
+    }
 }
 
 pub struct PollData<D> {
@@ -58,6 +63,13 @@ impl<D> PollData<D> {
         if let Some(ret) = ret {
             return Ok(ret);
         }
+        if let Some(source) = self
+            .id_source_map
+            .values()
+            .find(|source| source.borrow().has_pending_data())
+        {
+            return source.borrow_mut().read_data();
+        }
         let events = {
             let mut events = Vec::<libc::epoll_event>::with_capacity((self.counter * 4) as usize);
             let maxevents = events.capacity() as i32;
@@ -1,9 +1,10 @@
 pub mod key_mappings;
+use crate::logging::{self, log};
 use crate::{Error, Result};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::ffi::{c_char, c_int, c_void, CStr, CString};
-use std::sync::{Arc, Mutex, Once};
+use std::sync::{mpsc::Sender, Arc, Mutex, Once};
 
 static RIME_API_SETUP: Once = Once::new();
 
@@ -23,17 +24,17 @@ extern "C" {
         log_level: c_int,
     );
     fn c_do_maintenance(c_rime_api: *mut CRimeApi);
+    fn c_start_full_maintenance(c_rime_api: *mut CRimeApi) -> c_int;
+    fn c_join_maintenance_thread(c_rime_api: *mut CRimeApi);
     fn c_destory_rime_api(rime_api: *mut CRimeApi) -> c_void;
     #[allow(dead_code)]
     fn c_get_user_data_dir(rime_api: *mut CRimeApi) -> *mut std::ffi::c_char;
     #[allow(dead_code)]
     fn c_get_shared_data_dir(rime_api: *mut CRimeApi) -> *mut std::ffi::c_char;
-    #[allow(dead_code)]
     fn c_get_schema_list(
         rime_api: *mut CRimeApi,
         schema_list: *mut CRimeSchemaList,
     ) -> std::ffi::c_int;
-    #[allow(dead_code)]
     fn c_free_schema_list(rime_api: *mut CRimeApi, schema_list: *mut CRimeSchemaList) -> c_void;
     fn c_create_session(rime_api: *mut CRimeApi) -> usize;
     fn c_destory_session(rime_api: *mut CRimeApi, session_id: usize) -> c_void;
@@ -55,12 +56,24 @@ extern "C" {
         keycode: c_int,
         mask: c_int,
     ) -> c_int;
+    fn c_simulate_key_sequence(
+        rime_api: *mut CRimeApi,
+        session_id: usize,
+        key_sequence: *const c_char,
+    ) -> c_int;
     fn c_get_context(
         rime_api: *mut CRimeApi,
         session_id: usize,
         context: *mut CRimecmdRimeContext,
     ) -> c_void;
     fn c_free_context(context: *mut CRimecmdRimeContext) -> c_void;
+    fn c_set_option(
+        rime_api: *mut CRimeApi,
+        session_id: usize,
+        option: *const c_char,
+        value: c_int,
+    ) -> c_void;
+    fn c_get_option(rime_api: *mut CRimeApi, session_id: usize, option: *const c_char) -> c_int;
     fn c_get_current_schema(
         rime_api: *mut CRimeApi,
         session_id: usize,
@@ -70,10 +83,16 @@ extern "C" {
         // However, correct enough here.
         buffer_size: usize,
     ) -> c_int;
-    fn c_candidate_list_begin(
+    fn c_select_schema(
+        rime_api: *mut CRimeApi,
+        session_id: usize,
+        schema_id: *const c_char,
+    ) -> c_int;
+    fn c_candidate_list_from_index(
         rime_api: *mut CRimeApi,
         session_id: usize,
         iterator: *mut CRimeCandidateListIterator,
+        index: c_int,
     ) -> c_int;
     fn c_candidate_list_next(
         rime_api: *mut CRimeApi,
@@ -83,12 +102,43 @@ extern "C" {
         rime_api: *mut CRimeApi,
         iterator: *mut CRimeCandidateListIterator,
     ) -> c_void;
+    fn c_select_candidate(rime_api: *mut CRimeApi, session_id: usize, index: usize) -> c_int;
+    fn c_select_candidate_on_current_page(
+        rime_api: *mut CRimeApi,
+        session_id: usize,
+        index: usize,
+    ) -> c_int;
+    fn c_delete_candidate(rime_api: *mut CRimeApi, session_id: usize, index: usize) -> c_int;
+    fn c_delete_candidate_on_current_page(
+        rime_api: *mut CRimeApi,
+        session_id: usize,
+        index: usize,
+    ) -> c_int;
+    fn c_change_page(rime_api: *mut CRimeApi, session_id: usize, backward: c_int) -> c_int;
+    fn c_get_input(rime_api: *mut CRimeApi, session_id: usize) -> *const c_char;
+    fn c_set_input(rime_api: *mut CRimeApi, session_id: usize, input: *const c_char) -> c_int;
+    // currently, in Rust usize is conventionally used for size_t in C.
+    // According to standards, this is not perfectly correct.
+    // However, correct enough here.
+    fn c_get_caret_pos(rime_api: *mut CRimeApi, session_id: usize) -> usize;
+    fn c_set_caret_pos(rime_api: *mut CRimeApi, session_id: usize, caret_pos: usize) -> c_void;
+    fn c_commit_composition(rime_api: *mut CRimeApi, session_id: usize) -> c_int;
     fn c_clear_composition(rime_api: *mut CRimeApi, session_id: usize) -> c_void;
     fn c_rime_config_open(
         rime_api: *mut CRimeApi,
         config_id: *const c_char,
         config: *mut CRimeConfig,
     ) -> c_int;
+    fn c_rime_schema_open(
+        rime_api: *mut CRimeApi,
+        schema_id: *const c_char,
+        config: *mut CRimeConfig,
+    ) -> c_int;
+    fn c_rime_user_config_open(
+        rime_api: *mut CRimeApi,
+        config_id: *const c_char,
+        config: *mut CRimeConfig,
+    ) -> c_int;
     fn c_rime_config_close(rime_api: *mut CRimeApi, config: *mut CRimeConfig) -> c_int;
     fn c_rime_config_get_int(
         rime_api: *mut CRimeApi,
@@ -96,6 +146,75 @@ extern "C" {
         key: *const c_char,
         value: *mut c_int,
     ) -> c_int;
+    fn c_rime_config_get_bool(
+        rime_api: *mut CRimeApi,
+        config: *mut CRimeConfig,
+        key: *const c_char,
+        value: *mut c_int,
+    ) -> c_int;
+    fn c_rime_config_get_double(
+        rime_api: *mut CRimeApi,
+        config: *mut CRimeConfig,
+        key: *const c_char,
+        value: *mut f64,
+    ) -> c_int;
+    fn c_rime_config_get_cstring(
+        rime_api: *mut CRimeApi,
+        config: *mut CRimeConfig,
+        key: *const c_char,
+    ) -> *const c_char;
+    fn c_rime_config_begin_map(
+        rime_api: *mut CRimeApi,
+        iterator: *mut CRimeConfigIterator,
+        config: *mut CRimeConfig,
+        key: *const c_char,
+    ) -> c_int;
+    fn c_rime_config_begin_list(
+        rime_api: *mut CRimeApi,
+        iterator: *mut CRimeConfigIterator,
+        config: *mut CRimeConfig,
+        key: *const c_char,
+    ) -> c_int;
+    fn c_rime_config_next(rime_api: *mut CRimeApi, iterator: *mut CRimeConfigIterator) -> c_int;
+    fn c_rime_config_end(rime_api: *mut CRimeApi, iterator: *mut CRimeConfigIterator) -> c_void;
+    fn c_set_notification_handler(
+        rime_api: *mut CRimeApi,
+        handler: Option<CRimeNotificationHandler>,
+        context_object: *mut c_void,
+    ) -> c_void;
+    fn c_custom_settings_init(
+        rime_api: *mut CRimeApi,
+        config_id: *const c_char,
+    ) -> *mut CRimeCustomSettings;
+    fn c_custom_settings_destroy(
+        rime_api: *mut CRimeApi,
+        settings: *mut CRimeCustomSettings,
+    ) -> c_void;
+    fn c_customize_bool(
+        rime_api: *mut CRimeApi,
+        settings: *mut CRimeCustomSettings,
+        key: *const c_char,
+        value: c_int,
+    ) -> c_int;
+    fn c_customize_int(
+        rime_api: *mut CRimeApi,
+        settings: *mut CRimeCustomSettings,
+        key: *const c_char,
+        value: c_int,
+    ) -> c_int;
+    fn c_customize_double(
+        rime_api: *mut CRimeApi,
+        settings: *mut CRimeCustomSettings,
+        key: *const c_char,
+        value: f64,
+    ) -> c_int;
+    fn c_customize_string(
+        rime_api: *mut CRimeApi,
+        settings: *mut CRimeCustomSettings,
+        key: *const c_char,
+        value: *const c_char,
+    ) -> c_int;
+    fn c_save_settings(rime_api: *mut CRimeApi, settings: *mut CRimeCustomSettings) -> c_int;
 }
 
 #[repr(C)]
@@ -108,6 +227,15 @@ pub struct RimeConfig {
     rime_api: Arc<Mutex<RimeApi>>,
 }
 
+#[repr(C)]
+struct CRimeConfigIterator {
+    list: *mut c_void,
+    map: *mut c_void,
+    index: c_int,
+    key: *const c_char,
+    path: *const c_char,
+}
+
 pub trait RimeConfigValue
 where
     Self: Sized + Serialize,
@@ -129,10 +257,182 @@ impl RimeConfigValue for isize {
     }
 }
 
+impl RimeConfigValue for bool {
+    fn load(config: &mut RimeConfig, key: impl AsRef<str>) -> Option<Self> {
+        let mut mem: c_int = 0;
+        let key = CString::new(key.as_ref()).unwrap();
+        let c_rime_api = config.rime_api.lock().unwrap().c_rime_api;
+        let c_config = &mut config.c;
+        if 0 == unsafe { c_rime_config_get_bool(c_rime_api, c_config, key.as_ptr(), &mut mem) } {
+            None
+        } else {
+            Some(mem != 0)
+        }
+    }
+}
+
+impl RimeConfigValue for f64 {
+    fn load(config: &mut RimeConfig, key: impl AsRef<str>) -> Option<Self> {
+        let mut mem: f64 = 0.0;
+        let key = CString::new(key.as_ref()).unwrap();
+        let c_rime_api = config.rime_api.lock().unwrap().c_rime_api;
+        let c_config = &mut config.c;
+        if 0 == unsafe { c_rime_config_get_double(c_rime_api, c_config, key.as_ptr(), &mut mem) } {
+            None
+        } else {
+            Some(mem)
+        }
+    }
+}
+
+impl RimeConfigValue for String {
+    fn load(config: &mut RimeConfig, key: impl AsRef<str>) -> Option<Self> {
+        let key = CString::new(key.as_ref()).unwrap();
+        let c_rime_api = config.rime_api.lock().unwrap().c_rime_api;
+        let c_config = &mut config.c;
+        let value = unsafe { c_rime_config_get_cstring(c_rime_api, c_config, key.as_ptr()) };
+        (!value.is_null()).then(|| {
+            unsafe { CStr::from_ptr(value) }
+                .to_owned()
+                .into_string()
+                .unwrap()
+        })
+    }
+}
+
+/// Where a config is opened from.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    /// A deployed config, such as `default`, from the build directory.
+    #[default]
+    Config,
+    /// The deployed schema with the given schema ID, such as `luna_pinyin`.
+    Schema,
+    /// A config in the user data directory, such as `user`, as is.
+    /// These are not deployed, and no patches are applied to them.
+    User,
+}
+
+/// The types of config values that can be read. Rime stores every scalar
+/// as a string, and the type decides how that string is interpreted.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigValueType {
+    Integer,
+    Bool,
+    Double,
+    String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigValue {
+    Integer(isize),
+    Bool(bool),
+    Double(f64),
+    String(String),
+}
+
+impl ConfigValue {
+    pub fn value_type(&self) -> ConfigValueType {
+        match self {
+            ConfigValue::Integer(_) => ConfigValueType::Integer,
+            ConfigValue::Bool(_) => ConfigValueType::Bool,
+            ConfigValue::Double(_) => ConfigValueType::Double,
+            ConfigValue::String(_) => ConfigValueType::String,
+        }
+    }
+}
+
+/// A `ConfigValue` converted to what librime takes.
+enum CConfigValue {
+    Integer(c_int),
+    Bool(c_int),
+    Double(f64),
+    String(CString),
+}
+
 impl RimeConfig {
     pub fn get<V: RimeConfigValue>(&mut self, key: impl AsRef<str>) -> Option<V> {
         V::load(self, key)
     }
+
+    /// Read the whole subtree at `key`, with maps as JSON objects and lists
+    /// as JSON arrays. Scalars are kept as JSON strings, which is how Rime
+    /// stores them. Use `get` to read a scalar as another type. Entries that
+    /// cannot be read as a string, such as null ones, are JSON nulls. `None`
+    /// if there is nothing at `key`.
+    pub fn dump(&mut self, key: impl AsRef<str>) -> Option<serde_json::Value> {
+        match self.dump_entry(key.as_ref()) {
+            serde_json::Value::Null => None,
+            value => Some(value),
+        }
+    }
+
+    fn dump_entry(&mut self, key: &str) -> serde_json::Value {
+        if let Some(entries) = self.children(key, false) {
+            serde_json::Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, path)| (key, self.dump_entry(&path)))
+                    .collect(),
+            )
+        } else if let Some(entries) = self.children(key, true) {
+            serde_json::Value::Array(
+                entries
+                    .into_iter()
+                    .map(|(_, path)| self.dump_entry(&path))
+                    .collect(),
+            )
+        } else {
+            self.get::<String>(key)
+                .map_or(serde_json::Value::Null, serde_json::Value::String)
+        }
+    }
+
+    /// List the keys and full paths of the entries of the map, or the list,
+    /// at `key`. `None` if there is not a map, or a list, at `key`.
+    fn children(&mut self, key: &str, list: bool) -> Option<Vec<(String, String)>> {
+        let mut iterator = CRimeConfigIterator {
+            list: std::ptr::null_mut(),
+            map: std::ptr::null_mut(),
+            index: 0,
+            key: std::ptr::null(),
+            path: std::ptr::null(),
+        };
+        let key = CString::new(key).unwrap();
+        let c_rime_api = self.rime_api.lock().unwrap().c_rime_api;
+        let c_config = &mut self.c;
+        if 0 == unsafe {
+            if list {
+                c_rime_config_begin_list(c_rime_api, &mut iterator, c_config, key.as_ptr())
+            } else {
+                c_rime_config_begin_map(c_rime_api, &mut iterator, c_config, key.as_ptr())
+            }
+        } {
+            return None;
+        }
+        let children = std::iter::from_fn(|| {
+            if 1 == unsafe { c_rime_config_next(c_rime_api, &mut iterator) } {
+                Some((
+                    unsafe { CStr::from_ptr(iterator.key) }
+                        .to_str()
+                        .unwrap()
+                        .to_owned(),
+                    unsafe { CStr::from_ptr(iterator.path) }
+                        .to_str()
+                        .unwrap()
+                        .to_owned(),
+                ))
+            } else {
+                None
+            }
+        })
+        .collect();
+        unsafe { c_rime_config_end(c_rime_api, &mut iterator) };
+        Some(children)
+    }
 }
 
 impl Drop for RimeConfig {
@@ -186,29 +486,87 @@ struct CRimecmdRimeComposition {
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub struct RimeComposition {
+    /// The positions below are byte offsets into `preedit`, as Rime
+    /// reports them. `char_positions` and `utf16_positions` have the same
+    /// positions counted in characters and in UTF-16 code units.
     pub length: usize,
     pub cursor_pos: usize,
     pub sel_start: usize,
     pub sel_end: usize,
     pub preedit: String,
+    pub char_positions: RimeCompositionPositions,
+    pub utf16_positions: RimeCompositionPositions,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct RimeCompositionPositions {
+    pub cursor_pos: usize,
+    pub sel_start: usize,
+    pub sel_end: usize,
+}
+
+impl RimeCompositionPositions {
+    /// Convert byte offsets into `preedit` with `count`, which gets the
+    /// length of the part of `preedit` before an offset.
+    fn from_byte_offsets(
+        preedit: &str,
+        cursor_pos: usize,
+        sel_start: usize,
+        sel_end: usize,
+        count: impl Fn(&str) -> usize,
+    ) -> Self {
+        // An offset inside a character counts as the start of the character.
+        let convert = |offset: usize| {
+            count(
+                (0..=offset.min(preedit.len()))
+                    .rev()
+                    .find_map(|offset| preedit.get(..offset))
+                    .unwrap_or_default(),
+            )
+        };
+        Self {
+            cursor_pos: convert(cursor_pos),
+            sel_start: convert(sel_start),
+            sel_end: convert(sel_end),
+        }
+    }
 }
 
 fn rime_composition_from_c(
     c_rimecmd_rime_composition: &CRimecmdRimeComposition,
 ) -> RimeComposition {
+    let cursor_pos = c_rimecmd_rime_composition.cursor_pos as usize;
+    let sel_start = c_rimecmd_rime_composition.sel_start as usize;
+    let sel_end = c_rimecmd_rime_composition.sel_end as usize;
+    let preedit: String = if c_rimecmd_rime_composition.preedit.is_null() {
+        "".into()
+    } else {
+        unsafe { std::ffi::CStr::from_ptr(c_rimecmd_rime_composition.preedit) }
+            .to_str()
+            .unwrap()
+            .to_owned()
+    };
     RimeComposition {
         length: c_rimecmd_rime_composition.length as usize,
-        cursor_pos: c_rimecmd_rime_composition.cursor_pos as usize,
-        sel_start: c_rimecmd_rime_composition.sel_start as usize,
-        sel_end: c_rimecmd_rime_composition.sel_end as usize,
-        preedit: if c_rimecmd_rime_composition.preedit.is_null() {
-            "".into()
-        } else {
-            unsafe { std::ffi::CStr::from_ptr(c_rimecmd_rime_composition.preedit) }
-                .to_str()
-                .unwrap()
-                .to_owned()
-        },
+        cursor_pos,
+        sel_start,
+        sel_end,
+        char_positions: RimeCompositionPositions::from_byte_offsets(
+            &preedit,
+            cursor_pos,
+            sel_start,
+            sel_end,
+            |text| text.chars().count(),
+        ),
+        utf16_positions: RimeCompositionPositions::from_byte_offsets(
+            &preedit,
+            cursor_pos,
+            sel_start,
+            sel_end,
+            |text| text.encode_utf16().count(),
+        ),
+        preedit,
     }
 }
 
@@ -217,6 +575,7 @@ struct CRimecmdRimeContext {
     composition: CRimecmdRimeComposition,
     menu: CRimeMenu,
     commit_text_preview: *mut c_char,
+    select_labels: *mut *mut c_char,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -233,6 +592,14 @@ pub struct RimeMenu {
     pub page_no: usize,
     pub highlighted_candidate_index: usize,
     pub is_last_page: bool,
+    pub page_size: usize,
+    /// The keys used to select candidates on the page, as configured in
+    /// `menu/alternative_select_keys`. `None` when the schema uses the
+    /// default, digits.
+    pub select_keys: Option<String>,
+    /// The label of each candidate in `candidates`, which is what the
+    /// user sees next to the candidate, and hints the key to select it.
+    pub labels: Vec<String>,
 }
 
 fn rime_candidate_from_c(c_rime_candidate: &CRimeCandidate) -> RimeCandidate {
@@ -250,7 +617,42 @@ fn rime_candidate_from_c(c_rime_candidate: &CRimeCandidate) -> RimeCandidate {
     }
 }
 
-fn get_rime_menu(c_rime_api: *mut CRimeApi, session_id: usize, menu: &CRimeMenu) -> RimeMenu {
+/// Determine the label of the candidate at `index` on the page, the same way
+/// as Rime's own front ends: configured labels go first, then select keys,
+/// then digits.
+fn rime_candidate_label(
+    index: usize,
+    select_keys: Option<&str>,
+    select_labels: *mut *mut c_char,
+) -> String {
+    if !select_labels.is_null() {
+        let label = unsafe { *select_labels.add(index) };
+        if !label.is_null() {
+            return unsafe { CStr::from_ptr(label) }
+                .to_owned()
+                .into_string()
+                .unwrap();
+        }
+    }
+    select_keys
+        .and_then(|select_keys| select_keys.chars().nth(index))
+        .map(|select_key| select_key.to_string())
+        .unwrap_or_else(|| ((index + 1) % 10).to_string())
+}
+
+/// Read at most `limit` candidates from the whole candidate list, not just
+/// the current page, starting from the one at `offset`.
+fn get_candidates(
+    c_rime_api: *mut CRimeApi,
+    session_id: usize,
+    offset: usize,
+    limit: usize,
+) -> Vec<RimeCandidate> {
+    // No candidate list is long enough to have candidates past what Rime
+    // can index.
+    let Ok(offset) = offset.try_into() else {
+        return vec![];
+    };
     let mut iterator = CRimeCandidateListIterator {
         ptr: std::ptr::null_mut(),
         index: 0,
@@ -260,31 +662,55 @@ fn get_rime_menu(c_rime_api: *mut CRimeApi, session_id: usize, menu: &CRimeMenu)
             reserved: std::ptr::null_mut(),
         },
     };
-    unsafe {
-        c_candidate_list_begin(c_rime_api, session_id, &mut iterator);
+    if 0 == unsafe { c_candidate_list_from_index(c_rime_api, session_id, &mut iterator, offset) } {
+        return vec![];
     }
+    let candidates = std::iter::from_fn(|| {
+        (1 == unsafe { c_candidate_list_next(c_rime_api, &mut iterator) })
+            .then(|| rime_candidate_from_c(&iterator.candidate))
+    })
+    .take(limit)
+    .collect();
+    unsafe { c_candidate_list_end(c_rime_api, &mut iterator) };
+    candidates
+}
+
+fn get_rime_menu(
+    c_rime_api: *mut CRimeApi,
+    session_id: usize,
+    menu: &CRimeMenu,
+    select_labels: *mut *mut c_char,
+) -> RimeMenu {
+    let select_keys = (!menu.select_keys.is_null()).then(|| {
+        unsafe { CStr::from_ptr(menu.select_keys) }
+            .to_owned()
+            .into_string()
+            .unwrap()
+    });
+    let candidates = get_candidates(
+        c_rime_api,
+        session_id,
+        (menu.page_size * menu.page_no) as usize,
+        menu.page_size as usize,
+    );
     RimeMenu {
         page_no: menu.page_no as usize,
         is_last_page: menu.is_last_page == 1,
         highlighted_candidate_index: menu.highlighted_candidate_index as usize,
-        candidates: std::iter::from_fn(|| {
-            if 1 == unsafe { c_candidate_list_next(c_rime_api, &mut iterator) } {
-                Some(rime_candidate_from_c(&iterator.candidate))
-            } else {
-                unsafe { c_candidate_list_end(c_rime_api, &mut iterator) };
-                None
-            }
-        })
-        .skip((menu.page_size * menu.page_no) as usize)
-        .take(menu.page_size as usize)
-        .collect(),
+        page_size: menu.page_size as usize,
+        labels: (0..candidates.len())
+            .map(|index| rime_candidate_label(index, select_keys.as_deref(), select_labels))
+            .collect(),
+        select_keys,
+        candidates,
     }
 }
 
 #[derive(Debug)]
 pub struct RimeContext {
-    #[allow(dead_code)]
-    pub commit_text_preview: String,
+    /// The text that would be committed if the composition was committed
+    /// now. `None` when there is no composition.
+    pub commit_text_preview: Option<String>,
     pub composition: RimeComposition,
     pub menu: RimeMenu,
 }
@@ -296,6 +722,19 @@ struct CRimeApi {
     marker: core::marker::PhantomData<(*mut u8, core::marker::PhantomPinned)>,
 }
 
+type CRimeNotificationHandler = extern "C" fn(
+    context_object: *mut c_void,
+    session_id: usize,
+    message_type: *const c_char,
+    message_value: *const c_char,
+);
+
+#[repr(C)]
+struct CRimeCustomSettings {
+    data: [u8; 0],
+    marker: core::marker::PhantomData<(*mut u8, core::marker::PhantomPinned)>,
+}
+
 #[repr(C)]
 struct CRimeSchemaListItem {
     schema_id: *mut std::ffi::c_char,
@@ -334,7 +773,8 @@ pub struct RimeCommit {
     pub text: Option<String>,
 }
 
-#[allow(dead_code)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub struct RimeStatus {
     pub schema_id: String,
     pub schema_name: String,
@@ -347,20 +787,79 @@ pub struct RimeStatus {
     pub is_ascii_punct: bool,
 }
 
-#[allow(dead_code)]
+/// The part of `RimeStatus` that a UI is expected to show all the time.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct RimeUiStatus {
+    pub schema_id: String,
+    pub is_ascii_mode: bool,
+    pub is_full_shape: bool,
+}
+
+impl From<RimeStatus> for RimeUiStatus {
+    fn from(status: RimeStatus) -> Self {
+        Self {
+            schema_id: status.schema_id,
+            is_ascii_mode: status.is_ascii_mode,
+            is_full_shape: status.is_full_shape,
+        }
+    }
+}
+
+impl RimeStatus {
+    /// The options that the status reports, by their names in Rime.
+    pub fn options(&self) -> [(&'static str, bool); 5] {
+        [
+            ("ascii_mode", self.is_ascii_mode),
+            ("full_shape", self.is_full_shape),
+            ("simplification", self.is_simplified),
+            ("traditional", self.is_traditional),
+            ("ascii_punct", self.is_ascii_punct),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
 pub struct RimeSchema {
-    schema_id: String,
-    name: String,
+    pub schema_id: String,
+    pub name: String,
+}
+
+/// A switch declared under `switches` in a schema, with the current values
+/// of its options.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum RimeSwitch {
+    /// A single option that is either on or off. `states` are the labels
+    /// for off and on, in that order.
+    Toggle {
+        name: String,
+        value: bool,
+        states: Vec<String>,
+    },
+    /// A group of options of which at most one is on. `states` are the
+    /// labels for the options, in the same order as `options`.
+    Radio {
+        options: Vec<RimeSwitchOption>,
+        states: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub struct RimeSwitchOption {
+    pub name: String,
+    pub value: bool,
 }
 
-#[allow(dead_code)]
 fn rime_schema_from_c(c_rime_schema_item: &CRimeSchemaListItem) -> RimeSchema {
     RimeSchema {
         schema_id: unsafe { std::ffi::CStr::from_ptr(c_rime_schema_item.schema_id) }
             .to_owned()
             .into_string()
             .unwrap(),
-        name: unsafe { std::ffi::CStr::from_ptr(c_rime_schema_item.schema_id) }
+        name: unsafe { std::ffi::CStr::from_ptr(c_rime_schema_item.name) }
             .to_owned()
             .into_string()
             .unwrap(),
@@ -388,12 +887,17 @@ impl RimeSession {
         Self { session_id, api }
     }
 
+    pub fn session_id(&self) -> usize {
+        self.session_id
+    }
+
     pub fn get_config_value<V: RimeConfigValue>(
         &self,
+        source: ConfigSource,
         config_id: impl AsRef<str>,
         option_key: impl AsRef<str>,
     ) -> Result<V> {
-        let Some(mut config) = self.open_config(config_id.as_ref()) else {
+        let Some(mut config) = self.open_config(source, config_id.as_ref()) else {
             return Err(Error::ConfigNotFound(config_id.as_ref().into()));
         };
         let Some(option_value) = config.get(option_key.as_ref()) else {
@@ -402,6 +906,123 @@ impl RimeSession {
         Ok(option_value)
     }
 
+    pub fn get_schema_list(&self) -> Vec<RimeSchema> {
+        self.api.lock().unwrap().get_schema_list()
+    }
+
+    pub fn dump_config(
+        &self,
+        source: ConfigSource,
+        config_id: impl AsRef<str>,
+        key: impl AsRef<str>,
+    ) -> Result<serde_json::Value> {
+        let Some(mut config) = self.open_config(source, config_id.as_ref()) else {
+            return Err(Error::ConfigNotFound(config_id.as_ref().into()));
+        };
+        let Some(value) = config.dump(key.as_ref()) else {
+            return Err(Error::OptionNotFound(key.as_ref().into()));
+        };
+        Ok(value)
+    }
+
+    pub fn get_typed_config_value(
+        &self,
+        source: ConfigSource,
+        config_id: impl AsRef<str>,
+        option_key: impl AsRef<str>,
+        value_type: ConfigValueType,
+    ) -> Result<ConfigValue> {
+        Ok(match value_type {
+            ConfigValueType::Integer => {
+                ConfigValue::Integer(self.get_config_value(source, config_id, option_key)?)
+            }
+            ConfigValueType::Bool => {
+                ConfigValue::Bool(self.get_config_value(source, config_id, option_key)?)
+            }
+            ConfigValueType::Double => {
+                ConfigValue::Double(self.get_config_value(source, config_id, option_key)?)
+            }
+            ConfigValueType::String => {
+                ConfigValue::String(self.get_config_value(source, config_id, option_key)?)
+            }
+        })
+    }
+
+    /// Persist `value` at `key` as a patch in `<config_id>.custom.yaml` under
+    /// the user data directory, and then deploy, so that the change takes
+    /// effect.
+    ///
+    /// The schema of this session is applied again after deployment, so
+    /// that this session picks up the change. Other sessions keep using
+    /// what they have loaded until they switch schema, and do nothing while
+    /// the deployment runs.
+    pub fn customize_config(
+        &self,
+        config_id: impl AsRef<str>,
+        key: impl AsRef<str>,
+        value: &ConfigValue,
+    ) -> Result<()> {
+        let c_config_id = CString::new(config_id.as_ref()).unwrap();
+        let c_key = CString::new(key.as_ref()).unwrap();
+        // Check the value before taking the lock, so that a bad value from a
+        // client cannot panic while holding it.
+        let invalid_value = || Error::InvalidConfigValue(key.as_ref().into());
+        let c_value = match value {
+            ConfigValue::Integer(value) => {
+                CConfigValue::Integer((*value).try_into().map_err(|_| invalid_value())?)
+            }
+            ConfigValue::Bool(value) => CConfigValue::Bool(*value as c_int),
+            ConfigValue::Double(value) => CConfigValue::Double(*value),
+            ConfigValue::String(value) => {
+                CConfigValue::String(CString::new(value.as_str()).map_err(|_| invalid_value())?)
+            }
+        };
+        let api = self.api.lock().unwrap();
+        let settings = unsafe { c_custom_settings_init(api.c_rime_api, c_config_id.as_ptr()) };
+        if settings.is_null() {
+            return Err(Error::ConfigNotSaved(config_id.as_ref().into()));
+        }
+        let customized = 1
+            == match &c_value {
+                CConfigValue::Integer(value) => unsafe {
+                    c_customize_int(api.c_rime_api, settings, c_key.as_ptr(), *value)
+                },
+                CConfigValue::Bool(value) => unsafe {
+                    c_customize_bool(api.c_rime_api, settings, c_key.as_ptr(), *value)
+                },
+                CConfigValue::Double(value) => unsafe {
+                    c_customize_double(api.c_rime_api, settings, c_key.as_ptr(), *value)
+                },
+                CConfigValue::String(value) => unsafe {
+                    c_customize_string(api.c_rime_api, settings, c_key.as_ptr(), value.as_ptr())
+                },
+            };
+        let saved = customized && 1 == unsafe { c_save_settings(api.c_rime_api, settings) };
+        unsafe { c_custom_settings_destroy(api.c_rime_api, settings) };
+        if !saved {
+            return Err(Error::ConfigNotSaved(config_id.as_ref().into()));
+        }
+        unsafe { c_start_full_maintenance(api.c_rime_api) };
+        let c_rime_api = api.c_rime_api;
+        // Deployment takes a while, and other sessions need not wait for
+        // it, because librime disables them until it finishes.
+        drop(api);
+        unsafe { c_join_maintenance_thread(c_rime_api) };
+        // The change is saved by now, so failing to apply it to this session
+        // is not a failure to save it.
+        let schema_id = self.get_status().schema_id;
+        if let Err(error) = self.select_schema(&schema_id) {
+            log!(
+                logging::LogLevel::Warning,
+                "cannot select the schema {} again after customizing {}: {:?}",
+                schema_id,
+                config_id.as_ref(),
+                error
+            );
+        }
+        Ok(())
+    }
+
     pub fn process_key(&self, keycode: usize, mask: usize) -> bool {
         let api = self.api.lock().unwrap();
         1 == unsafe {
@@ -414,7 +1035,18 @@ impl RimeSession {
         }
     }
 
-    #[allow(dead_code)]
+    /// Process a sequence of keys written in Rime's key sequence notation,
+    /// e.g. `{Control+grave}2nihao{space}`.
+    ///
+    /// Return false when the sequence cannot be parsed.
+    pub fn simulate_key_sequence(&self, key_sequence: impl AsRef<str>) -> bool {
+        let key_sequence = CString::new(key_sequence.as_ref()).unwrap();
+        let api = self.api.lock().unwrap();
+        1 == unsafe {
+            c_simulate_key_sequence(api.c_rime_api, self.session_id, key_sequence.as_ptr())
+        }
+    }
+
     pub fn get_current_schema(&self) -> String {
         let api = self.api.lock().unwrap();
         let mut buffer = [0; 1024];
@@ -429,10 +1061,16 @@ impl RimeSession {
             .unwrap()
     }
 
+    pub fn get_candidates(&self, offset: usize, limit: usize) -> Vec<RimeCandidate> {
+        let api = self.api.lock().unwrap();
+        get_candidates(api.c_rime_api, self.session_id, offset, limit)
+    }
+
     pub fn get_context(&self) -> RimeContext {
         let api = self.api.lock().unwrap();
         let mut c_context = CRimecmdRimeContext {
             commit_text_preview: std::ptr::null_mut(),
+            select_labels: std::ptr::null_mut(),
             composition: CRimecmdRimeComposition {
                 sel_end: 0,
                 sel_start: 0,
@@ -455,15 +1093,22 @@ impl RimeSession {
         }
         let context = RimeContext {
             commit_text_preview: if c_context.commit_text_preview.is_null() {
-                "".into()
+                None
             } else {
-                unsafe { std::ffi::CStr::from_ptr(c_context.commit_text_preview) }
-                    .to_str()
-                    .unwrap()
-                    .to_owned()
+                Some(
+                    unsafe { std::ffi::CStr::from_ptr(c_context.commit_text_preview) }
+                        .to_str()
+                        .unwrap()
+                        .to_owned(),
+                )
             },
             composition: rime_composition_from_c(&c_context.composition),
-            menu: get_rime_menu(api.c_rime_api, self.session_id, &c_context.menu),
+            menu: get_rime_menu(
+                api.c_rime_api,
+                self.session_id,
+                &c_context.menu,
+                c_context.select_labels,
+            ),
         };
         unsafe {
             c_free_context(&mut c_context);
@@ -538,18 +1183,188 @@ impl RimeSession {
         status
     }
 
+    /// List the switches of the current schema. Switches that cannot be
+    /// understood are skipped.
+    pub fn list_switches(&self) -> Vec<RimeSwitch> {
+        let Some(mut config) = self.open_config(ConfigSource::Schema, self.get_current_schema())
+        else {
+            return vec![];
+        };
+        let Some(serde_json::Value::Array(switches)) = config.dump("switches") else {
+            return vec![];
+        };
+        drop(config);
+        let strings = |value: Option<&serde_json::Value>| -> Vec<String> {
+            value
+                .and_then(serde_json::Value::as_array)
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(serde_json::Value::as_str)
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        switches
+            .iter()
+            .filter_map(|switch| {
+                let states = strings(switch.get("states"));
+                if let Some(name) = switch.get("name").and_then(serde_json::Value::as_str) {
+                    Some(RimeSwitch::Toggle {
+                        name: name.into(),
+                        value: self.get_option(name),
+                        states,
+                    })
+                } else if switch.get("options").is_some() {
+                    Some(RimeSwitch::Radio {
+                        options: strings(switch.get("options"))
+                            .into_iter()
+                            .map(|name| RimeSwitchOption {
+                                value: self.get_option(&name),
+                                name,
+                            })
+                            .collect(),
+                        states,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    pub fn get_option(&self, option: impl AsRef<str>) -> bool {
+        let option = CString::new(option.as_ref()).unwrap();
+        let api = self.api.lock().unwrap();
+        1 == unsafe { c_get_option(api.c_rime_api, self.session_id, option.as_ptr()) }
+    }
+
+    pub fn set_option(&self, option: impl AsRef<str>, value: bool) {
+        let option = CString::new(option.as_ref()).unwrap();
+        let api = self.api.lock().unwrap();
+        unsafe {
+            c_set_option(
+                api.c_rime_api,
+                self.session_id,
+                option.as_ptr(),
+                value as c_int,
+            )
+        };
+    }
+
+    /// Rime itself accepts any schema id, even one without a schema
+    /// behind it, so the id is checked against the schema list first.
+    pub fn select_schema(&self, schema_id: impl AsRef<str>) -> Result<()> {
+        if !self
+            .get_schema_list()
+            .iter()
+            .any(|schema| schema.schema_id == schema_id.as_ref())
+        {
+            return Err(Error::SchemaNotFound(schema_id.as_ref().into()));
+        }
+        let c_schema_id = CString::new(schema_id.as_ref()).unwrap();
+        let api = self.api.lock().unwrap();
+        if 0 == unsafe { c_select_schema(api.c_rime_api, self.session_id, c_schema_id.as_ptr()) } {
+            return Err(Error::SchemaNotFound(schema_id.as_ref().into()));
+        }
+        Ok(())
+    }
+
+    /// * `on_current_page` - when true, `index` counts from the first
+    ///   candidate on the current page of the menu. Otherwise, it counts
+    ///   from the first candidate of the whole candidate list.
+    pub fn select_candidate(&self, index: usize, on_current_page: bool) -> bool {
+        let api = self.api.lock().unwrap();
+        1 == if on_current_page {
+            unsafe { c_select_candidate_on_current_page(api.c_rime_api, self.session_id, index) }
+        } else {
+            unsafe { c_select_candidate(api.c_rime_api, self.session_id, index) }
+        }
+    }
+
+    /// Remove a candidate the user has taught Rime from the user dictionary.
+    ///
+    /// `index` and `on_current_page` work the same as in `select_candidate`.
+    pub fn delete_candidate(&self, index: usize, on_current_page: bool) -> bool {
+        let api = self.api.lock().unwrap();
+        1 == if on_current_page {
+            unsafe { c_delete_candidate_on_current_page(api.c_rime_api, self.session_id, index) }
+        } else {
+            unsafe { c_delete_candidate(api.c_rime_api, self.session_id, index) }
+        }
+    }
+
+    pub fn change_page(&self, backward: bool) -> bool {
+        let api = self.api.lock().unwrap();
+        1 == unsafe { c_change_page(api.c_rime_api, self.session_id, backward as c_int) }
+    }
+
+    /// Get the raw input, i.e. what the user has typed for the composition,
+    /// before it gets converted into the preedit.
+    pub fn get_input(&self) -> String {
+        let api = self.api.lock().unwrap();
+        let input = unsafe { c_get_input(api.c_rime_api, self.session_id) };
+        if input.is_null() {
+            "".into()
+        } else {
+            // The string is owned by Rime, and only valid until the input
+            // changes. The lock held here makes sure it does not change
+            // before it is copied.
+            unsafe { CStr::from_ptr(input) }
+                .to_str()
+                .unwrap()
+                .to_owned()
+        }
+    }
+
+    pub fn set_input(&self, input: impl AsRef<str>) -> bool {
+        let Ok(input) = CString::new(input.as_ref()) else {
+            return false;
+        };
+        let api = self.api.lock().unwrap();
+        1 == unsafe { c_set_input(api.c_rime_api, self.session_id, input.as_ptr()) }
+    }
+
+    /// The caret position is a byte offset into the raw input.
+    pub fn get_caret_pos(&self) -> usize {
+        let api = self.api.lock().unwrap();
+        unsafe { c_get_caret_pos(api.c_rime_api, self.session_id) }
+    }
+
+    pub fn set_caret_pos(&self, caret_pos: usize) {
+        let api = self.api.lock().unwrap();
+        unsafe { c_set_caret_pos(api.c_rime_api, self.session_id, caret_pos) };
+    }
+
+    /// Commit what is currently composed, as if the user confirmed it.
+    /// The committed text is available through `get_commit` afterwards.
+    pub fn commit_composition(&self) -> bool {
+        let api = self.api.lock().unwrap();
+        1 == unsafe { c_commit_composition(api.c_rime_api, self.session_id) }
+    }
+
     pub fn clear_composition(&self) {
         let api = self.api.lock().unwrap();
         unsafe { c_clear_composition(api.c_rime_api, self.session_id) };
     }
 
-    pub fn open_config(&self, config_id: impl AsRef<str>) -> Option<RimeConfig> {
+    pub fn open_config(
+        &self,
+        source: ConfigSource,
+        config_id: impl AsRef<str>,
+    ) -> Option<RimeConfig> {
         let mut c_config = CRimeConfig {
             ptr: std::ptr::null_mut(),
         };
         let config_id = CString::new(config_id.as_ref()).unwrap();
         let c_rime_api = self.api.lock().unwrap().c_rime_api;
-        if 0 == unsafe { c_rime_config_open(c_rime_api, config_id.as_ptr(), &mut c_config) } {
+        let c_open = match source {
+            ConfigSource::Config => c_rime_config_open,
+            ConfigSource::Schema => c_rime_schema_open,
+            ConfigSource::User => c_rime_user_config_open,
+        };
+        if 0 == unsafe { c_open(c_rime_api, config_id.as_ptr(), &mut c_config) } {
             None
         } else {
             Some(RimeConfig {
@@ -569,6 +1384,40 @@ impl Drop for RimeSession {
     }
 }
 
+/// A message Rime sends on its own, such as when deployment starts or
+/// finishes, when a schema is selected, or when an option is changed.
+/// See `RimeNotificationHandler` in `rime_api.h` for the message types and
+/// values.
+#[derive(Debug, Clone)]
+pub struct RimeNotification {
+    /// The session the message is about. Messages that are not about a
+    /// session, such as `deploy`, have the session that was current in Rime,
+    /// which is meaningless.
+    pub session_id: usize,
+    pub message_type: String,
+    pub message_value: String,
+}
+
+extern "C" fn notification_handler(
+    context_object: *mut c_void,
+    session_id: usize,
+    message_type: *const c_char,
+    message_value: *const c_char,
+) {
+    let sender = unsafe { &*(context_object as *const Sender<RimeNotification>) };
+    let notification = RimeNotification {
+        session_id,
+        message_type: unsafe { CStr::from_ptr(message_type) }
+            .to_string_lossy()
+            .into_owned(),
+        message_value: unsafe { CStr::from_ptr(message_value) }
+            .to_string_lossy()
+            .into_owned(),
+    };
+    // Nobody listening to the notifications is not a problem of Rime's.
+    let _ = sender.send(notification);
+}
+
 pub struct RimeApi {
     c_rime_api: *mut CRimeApi,
     // The pointers of the strings below are passed to Rime and kept there,
@@ -576,6 +1425,8 @@ pub struct RimeApi {
     // same memory location.
     _user_data_dir: std::boxed::Box<std::ffi::CString>,
     _shared_data_dir: std::boxed::Box<std::ffi::CString>,
+    // Same as above, the pointer to this is passed to Rime.
+    notification_sender: Option<std::boxed::Box<Sender<RimeNotification>>>,
 }
 
 unsafe impl Send for RimeApi {
@@ -587,6 +1438,9 @@ unsafe impl Send for RimeApi {
 
 impl Drop for RimeApi {
     fn drop(&mut self) {
+        if self.notification_sender.is_some() {
+            unsafe { c_set_notification_handler(self.c_rime_api, None, std::ptr::null_mut()) };
+        }
         unsafe { c_destory_rime_api(self.c_rime_api) };
     }
 }
@@ -641,10 +1495,24 @@ impl RimeApi {
             },
             _user_data_dir: user_data_dir,
             _shared_data_dir: shared_data_dir,
+            notification_sender: None,
         }
     }
 
-    #[allow(dead_code)]
+    /// Have Rime's notifications sent to `sender`. Rime keeps only one
+    /// handler, so this replaces the sender set before, if any.
+    pub fn set_notification_handler(&mut self, sender: Sender<RimeNotification>) {
+        let sender = std::boxed::Box::new(sender);
+        unsafe {
+            c_set_notification_handler(
+                self.c_rime_api,
+                Some(notification_handler),
+                &*sender as *const Sender<RimeNotification> as *mut c_void,
+            )
+        };
+        self.notification_sender = Some(sender);
+    }
+
     pub fn get_schema_list(&self) -> Vec<RimeSchema> {
         let mut schema_list = CRimeSchemaList {
             size: 0,
@@ -691,6 +1559,7 @@ pub enum LogLevel {
 
 #[cfg(test)]
 mod test {
+    use crate::rime_api::ConfigSource;
     use crate::testing_utilities::{temporary_directory_path, LOG_LEVEL};
     use std::sync::{Arc, Mutex};
 
@@ -768,10 +1637,346 @@ mod test {
             LOG_LEVEL,
         );
         let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
-        let mut rime_config = rime_session.open_config("default").unwrap();
+        let mut rime_config = rime_session
+            .open_config(ConfigSource::Config, "default")
+            .unwrap();
         assert_eq!(5, rime_config.get::<isize>("menu/page_size").unwrap());
     }
 
+    #[test]
+    #[ignore = "not thread safe"]
+    fn get_config_values_of_other_types() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let mut rime_config = rime_session
+            .open_config(ConfigSource::Config, "default")
+            .unwrap();
+        assert!(rime_config.get::<bool>("switcher/fold_options").unwrap());
+        assert_eq!(0.4, rime_config.get::<f64>("config_version").unwrap());
+        assert_eq!(
+            "〔方案選單〕",
+            rime_config.get::<String>("switcher/caption").unwrap()
+        );
+        assert!(rime_config.get::<String>("no/such/key").is_none());
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn open_config_from_other_sources() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        assert_eq!(
+            "ascii_mode",
+            rime_session
+                .get_config_value::<String>(ConfigSource::Schema, "luna_pinyin", "switches/@0/name")
+                .unwrap()
+        );
+        // installation.yaml is written to the user data directory on deployment.
+        assert!(rime_session
+            .get_config_value::<String>(ConfigSource::User, "installation", "installation_id")
+            .is_ok());
+    }
+
+    #[test]
+    fn composition_positions() {
+        // "𝝙" takes 4 bytes in UTF-8, and 2 code units in UTF-16.
+        let preedit = "骂𝝙a";
+        assert_eq!(
+            crate::rime_api::RimeCompositionPositions {
+                cursor_pos: 3,
+                sel_start: 1,
+                sel_end: 2,
+            },
+            crate::rime_api::RimeCompositionPositions::from_byte_offsets(
+                preedit,
+                8,
+                3,
+                7,
+                |text| text.chars().count()
+            ),
+        );
+        assert_eq!(
+            crate::rime_api::RimeCompositionPositions {
+                cursor_pos: 4,
+                sel_start: 1,
+                sel_end: 3,
+            },
+            crate::rime_api::RimeCompositionPositions::from_byte_offsets(
+                preedit,
+                8,
+                3,
+                7,
+                |text| text.encode_utf16().count()
+            ),
+        );
+        // Offsets inside a character, or past the end.
+        assert_eq!(
+            crate::rime_api::RimeCompositionPositions {
+                cursor_pos: 3,
+                sel_start: 0,
+                sel_end: 1,
+            },
+            crate::rime_api::RimeCompositionPositions::from_byte_offsets(
+                preedit,
+                20,
+                2,
+                5,
+                |text| text.chars().count()
+            ),
+        );
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn get_candidates() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        assert!(rime_session.get_candidates(0, 30).is_empty());
+        rime_session.process_key(109 /* m */, 0);
+        let candidates = rime_session.get_candidates(0, 30);
+        assert_eq!(30, candidates.len());
+        assert_eq!("骂", candidates[0].text);
+        let page_size = rime_session.get_context().menu.page_size;
+        assert_eq!(
+            candidates[page_size].text,
+            rime_session.get_candidates(page_size, 1)[0].text
+        );
+        assert!(rime_session.get_candidates(usize::MAX, 1).is_empty());
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn notification() {
+        let mut rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let (sender, receiver) = std::sync::mpsc::channel();
+        rime_api.set_notification_handler(sender);
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        rime_session.set_option("full_shape", true);
+        // Creating the session notifies of the schema first.
+        assert!(receiver.try_iter().any(|notification| {
+            notification.message_type == "option" && notification.message_value == "full_shape"
+        }));
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn list_switches() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        rime_session.set_option("full_shape", true);
+        let switches = rime_session.list_switches();
+        assert_eq!(4, switches.len());
+        let crate::rime_api::RimeSwitch::Toggle {
+            name,
+            value,
+            states,
+        } = &switches[1]
+        else {
+            panic!();
+        };
+        assert_eq!("full_shape", name);
+        assert!(value);
+        assert_eq!(&vec!["半角".to_owned(), "全角".to_owned()], states);
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn customize_config() {
+        let user_data_directory = temporary_directory_path();
+        let rime_api =
+            crate::rime_api::RimeApi::new(&user_data_directory, "./test_shared_data", LOG_LEVEL);
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        rime_session
+            .customize_config(
+                "default",
+                "menu/page_size",
+                &crate::rime_api::ConfigValue::Integer(7),
+            )
+            .unwrap();
+        assert!(std::path::Path::new(&user_data_directory)
+            .join("default.custom.yaml")
+            .exists());
+        let mut rime_config = rime_session
+            .open_config(ConfigSource::Config, "default")
+            .unwrap();
+        assert_eq!(7, rime_config.get::<isize>("menu/page_size").unwrap());
+        drop(rime_config);
+        rime_session.process_key(109 /* m */, 0);
+        assert_eq!(7, rime_session.get_context().menu.page_size);
+        // Right after the last deployment.
+        rime_session
+            .customize_config(
+                "default",
+                "menu/page_size",
+                &crate::rime_api::ConfigValue::Integer(8),
+            )
+            .unwrap();
+        assert_eq!(
+            8,
+            rime_session
+                .get_config_value::<isize>(ConfigSource::Config, "default", "menu/page_size")
+                .unwrap()
+        );
+        assert!(matches!(
+            rime_session.customize_config(
+                "default",
+                "menu/page_size",
+                &crate::rime_api::ConfigValue::String("\0".into()),
+            ),
+            Err(crate::Error::InvalidConfigValue(_))
+        ));
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn dump_config() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        assert_eq!(
+            serde_json::json!({ "page_size": "5" }),
+            rime_session
+                .dump_config(ConfigSource::Config, "default", "menu")
+                .unwrap()
+        );
+        assert_eq!(
+            serde_json::json!(["Control+grave", "Control+Shift+grave", "F4"]),
+            rime_session
+                .dump_config(ConfigSource::Config, "default", "switcher/hotkeys")
+                .unwrap()
+        );
+        assert!(rime_session
+            .dump_config(ConfigSource::Config, "default", "no/such/key")
+            .is_err());
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn get_schema_list() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        let schema_list = rime_session.get_schema_list();
+        assert_eq!(schema_list[0].schema_id, "luna_pinyin");
+        assert_eq!(schema_list[0].name, "朙月拼音");
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn select_schema() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        rime_session.select_schema("luna_pinyin_simp").unwrap();
+        assert_eq!("luna_pinyin_simp", rime_session.get_status().schema_id);
+        assert!(rime_session.select_schema("no_such_schema").is_err());
+        assert_eq!("luna_pinyin_simp", rime_session.get_status().schema_id);
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn set_option() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        assert!(!rime_session.get_option("ascii_mode"));
+        rime_session.set_option("ascii_mode", true);
+        assert!(rime_session.get_option("ascii_mode"));
+        assert!(rime_session.get_status().is_ascii_mode);
+        rime_session.set_option("ascii_mode", false);
+        assert!(!rime_session.get_option("ascii_mode"));
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn delete_missing_candidate() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        assert!(!rime_session.delete_candidate(0, true));
+        rime_session.process_key(109 /* m */, 0);
+        assert!(!rime_session.delete_candidate(10, true));
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn change_page() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        rime_session.process_key(109 /* m */, 0);
+        let first_page = rime_session.get_context().menu;
+        assert_eq!(0, first_page.page_no);
+        assert_eq!(5, first_page.page_size);
+        assert_eq!(vec!["1", "2", "3", "4", "5"], first_page.labels);
+        assert!(rime_session.change_page(false));
+        let second_page = rime_session.get_context().menu;
+        assert_eq!(1, second_page.page_no);
+        assert_ne!(
+            first_page.candidates[0].text,
+            second_page.candidates[0].text
+        );
+        assert!(rime_session.change_page(true));
+        assert_eq!(0, rime_session.get_context().menu.page_no);
+    }
+
+    #[test]
+    #[ignore = "not thread safe"]
+    fn set_input_and_caret_pos() {
+        let rime_api = crate::rime_api::RimeApi::new(
+            temporary_directory_path(),
+            "./test_shared_data",
+            LOG_LEVEL,
+        );
+        let rime_session = crate::rime_api::RimeSession::new(Arc::new(Mutex::new(rime_api)));
+        assert_eq!("", rime_session.get_input());
+        assert!(!rime_session.set_input("\0"));
+        assert!(rime_session.set_input("nihao"));
+        assert_eq!("nihao", rime_session.get_input());
+        assert_eq!(5, rime_session.get_caret_pos());
+        rime_session.set_caret_pos(2);
+        assert_eq!(2, rime_session.get_caret_pos());
+        assert_eq!("nihao", rime_session.get_input());
+    }
+
     #[test]
     #[ignore = "not thread safe"]
     fn clear_composition() {
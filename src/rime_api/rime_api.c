@@ -1,6 +1,7 @@
 /// Wrap Rime's C API to be more compatible with Rust's FFI.
 
 #include <rime_api.h>
+#include <rime_levers_api.h>
 #include <stdlib.h>
 #include <string.h>
 
@@ -39,8 +40,27 @@ void c_do_maintenance(RimeApi *rime_api) {
   }
 }
 
+// Deploy everything on librime's maintenance thread, without waiting for it.
+// Passing True skips looking for modified files first, which compares
+// modification times at a resolution of one second, and so might miss a
+// change made right after the last deployment. librime's own
+// start_maintenance_on_workspace_change is start_maintenance(False).
+Bool c_start_full_maintenance(RimeApi *rime_api) {
+  return rime_api->start_maintenance(True);
+}
+
+void c_join_maintenance_thread(RimeApi *rime_api) {
+  rime_api->join_maintenance_thread();
+}
+
 void c_destory_rime_api(RimeApi *rime_api) { rime_api->finalize(); }
 
+void c_set_notification_handler(RimeApi *rime_api,
+                                RimeNotificationHandler handler,
+                                void *context_object) {
+  rime_api->set_notification_handler(handler, context_object);
+}
+
 const char *c_get_user_data_dir(RimeApi *rime_api) {
   return rime_api->get_user_data_dir();
 }
@@ -66,8 +86,8 @@ void c_destory_session(RimeApi *rime_api, RimeSessionId session_id) {
 }
 
 typedef struct rimecmd_rime_status {
-  char *schema_name;
   char *schema_id;
+  char *schema_name;
   Bool is_disabled;
   Bool is_composing;
   Bool is_ascii_mode;
@@ -122,10 +142,17 @@ Bool c_process_key(RimeApi *rime_api, RimeSessionId session_id, int keycode,
   return rime_api->process_key(session_id, keycode, mask);
 }
 
+Bool c_simulate_key_sequence(RimeApi *rime_api, RimeSessionId session_id,
+                             const char *key_sequence) {
+  return rime_api->simulate_key_sequence(session_id, key_sequence);
+}
+
 typedef struct rimecmd_rime_context {
   RimeComposition composition;
   RimeMenu menu;
   char *commit_text_preview;
+  // When not NULL, this has `menu.page_size` elements.
+  char **select_labels;
 } RimecmdRimeContext;
 
 void c_get_context(RimeApi *rime_api, RimeSessionId session_id,
@@ -141,6 +168,20 @@ void c_get_context(RimeApi *rime_api, RimeSessionId session_id,
     rimecmd_context->composition.preedit =
         strdup(rime_context.composition.preedit);
   rimecmd_context->menu = rime_context.menu;
+  // The candidates are freed along with the context, and they are read
+  // through the candidate list iterator anyway.
+  rimecmd_context->menu.candidates = NULL;
+  if (rime_context.menu.select_keys)
+    rimecmd_context->menu.select_keys = strdup(rime_context.menu.select_keys);
+  if (rime_context.select_labels) {
+    rimecmd_context->select_labels =
+        calloc(rime_context.menu.page_size, sizeof(char *));
+    for (int i = 0; i < rime_context.menu.page_size; i++) {
+      if (rime_context.select_labels[i])
+        rimecmd_context->select_labels[i] =
+            strdup(rime_context.select_labels[i]);
+    }
+  }
   rime_api->free_context(&rime_context);
 }
 
@@ -149,6 +190,23 @@ void c_free_context(RimecmdRimeContext *context) {
     free(context->commit_text_preview);
   if (context->composition.preedit)
     free(context->composition.preedit);
+  if (context->menu.select_keys)
+    free(context->menu.select_keys);
+  if (context->select_labels) {
+    for (int i = 0; i < context->menu.page_size; i++)
+      free(context->select_labels[i]);
+    free(context->select_labels);
+  }
+}
+
+void c_set_option(RimeApi *rime_api, RimeSessionId session_id,
+                  const char *option, Bool value) {
+  rime_api->set_option(session_id, option, value);
+}
+
+Bool c_get_option(RimeApi *rime_api, RimeSessionId session_id,
+                  const char *option) {
+  return rime_api->get_option(session_id, option);
 }
 
 Bool c_get_current_schema(RimeApi *rime_api, RimeSessionId session_id,
@@ -156,9 +214,15 @@ Bool c_get_current_schema(RimeApi *rime_api, RimeSessionId session_id,
   return rime_api->get_current_schema(session_id, schema_id, buffer_size);
 }
 
-Bool c_candidate_list_begin(RimeApi *rime_api, RimeSessionId session_id,
-                            RimeCandidateListIterator *iterator) {
-  return rime_api->candidate_list_begin(session_id, iterator);
+Bool c_select_schema(RimeApi *rime_api, RimeSessionId session_id,
+                     const char *schema_id) {
+  return rime_api->select_schema(session_id, schema_id);
+}
+
+Bool c_candidate_list_from_index(RimeApi *rime_api, RimeSessionId session_id,
+                                 RimeCandidateListIterator *iterator,
+                                 int index) {
+  return rime_api->candidate_list_from_index(session_id, iterator, index);
 }
 
 Bool c_candidate_list_next(RimeApi *rime_api,
@@ -171,20 +235,167 @@ void c_candidate_list_end(RimeApi *rime_api,
   rime_api->candidate_list_end(iterator);
 }
 
+Bool c_select_candidate(RimeApi *rime_api, RimeSessionId session_id,
+                        size_t index) {
+  return rime_api->select_candidate(session_id, index);
+}
+
+Bool c_select_candidate_on_current_page(RimeApi *rime_api,
+                                        RimeSessionId session_id,
+                                        size_t index) {
+  return rime_api->select_candidate_on_current_page(session_id, index);
+}
+
+Bool c_delete_candidate(RimeApi *rime_api, RimeSessionId session_id,
+                        size_t index) {
+  return rime_api->delete_candidate(session_id, index);
+}
+
+Bool c_delete_candidate_on_current_page(RimeApi *rime_api,
+                                        RimeSessionId session_id,
+                                        size_t index) {
+  return rime_api->delete_candidate_on_current_page(session_id, index);
+}
+
+Bool c_change_page(RimeApi *rime_api, RimeSessionId session_id,
+                   Bool backward) {
+  return rime_api->change_page(session_id, backward);
+}
+
+const char *c_get_input(RimeApi *rime_api, RimeSessionId session_id) {
+  return rime_api->get_input(session_id);
+}
+
+Bool c_set_input(RimeApi *rime_api, RimeSessionId session_id,
+                 const char *input) {
+  return rime_api->set_input(session_id, input);
+}
+
+size_t c_get_caret_pos(RimeApi *rime_api, RimeSessionId session_id) {
+  return rime_api->get_caret_pos(session_id);
+}
+
+void c_set_caret_pos(RimeApi *rime_api, RimeSessionId session_id,
+                     size_t caret_pos) {
+  rime_api->set_caret_pos(session_id, caret_pos);
+}
+
+Bool c_commit_composition(RimeApi *rime_api, RimeSessionId session_id) {
+  return rime_api->commit_composition(session_id);
+}
+
 void c_clear_composition(RimeApi *rime_api, RimeSessionId session_id) {
   rime_api->clear_composition(session_id);
 }
 
-void c_rime_config_open(RimeApi *rime_api, const char *config_id,
+Bool c_rime_config_open(RimeApi *rime_api, const char *config_id,
                         RimeConfig *config) {
-  rime_api->config_open(config_id, config);
+  return rime_api->config_open(config_id, config);
 }
 
-void c_rime_config_close(RimeApi *rime_api, RimeConfig *config) {
-  rime_api->config_close(config);
+Bool c_rime_schema_open(RimeApi *rime_api, const char *schema_id,
+                        RimeConfig *config) {
+  return rime_api->schema_open(schema_id, config);
 }
 
-void c_rime_config_get_int(RimeApi *rime_api, RimeConfig *config,
+Bool c_rime_user_config_open(RimeApi *rime_api, const char *config_id,
+                             RimeConfig *config) {
+  return rime_api->user_config_open(config_id, config);
+}
+
+Bool c_rime_config_close(RimeApi *rime_api, RimeConfig *config) {
+  return rime_api->config_close(config);
+}
+
+Bool c_rime_config_get_int(RimeApi *rime_api, RimeConfig *config,
                            const char *key, int *value) {
-  rime_api->config_get_int(config, key, value);
+  return rime_api->config_get_int(config, key, value);
+}
+
+Bool c_rime_config_get_bool(RimeApi *rime_api, RimeConfig *config,
+                            const char *key, Bool *value) {
+  return rime_api->config_get_bool(config, key, value);
+}
+
+Bool c_rime_config_get_double(RimeApi *rime_api, RimeConfig *config,
+                              const char *key, double *value) {
+  return rime_api->config_get_double(config, key, value);
+}
+
+// `config_get_cstring` is used instead of `config_get_string`, so that
+// the length of the string is not limited by a buffer size.
+// The returned string is owned by the config.
+const char *c_rime_config_get_cstring(RimeApi *rime_api, RimeConfig *config,
+                                      const char *key) {
+  return rime_api->config_get_cstring(config, key);
+}
+
+Bool c_rime_config_begin_map(RimeApi *rime_api, RimeConfigIterator *iterator,
+                             RimeConfig *config, const char *key) {
+  return rime_api->config_begin_map(iterator, config, key);
+}
+
+Bool c_rime_config_begin_list(RimeApi *rime_api, RimeConfigIterator *iterator,
+                              RimeConfig *config, const char *key) {
+  return rime_api->config_begin_list(iterator, config, key);
+}
+
+Bool c_rime_config_next(RimeApi *rime_api, RimeConfigIterator *iterator) {
+  return rime_api->config_next(iterator);
+}
+
+void c_rime_config_end(RimeApi *rime_api, RimeConfigIterator *iterator) {
+  rime_api->config_end(iterator);
+}
+
+// The "levers" module is the part of Rime that deployment tools, such as
+// the settings UI of Rime's front ends, are built on. It writes changes
+// to `<config_id>.custom.yaml` in the user data directory as patches.
+static RimeLeversApi *c_get_levers_api(RimeApi *rime_api) {
+  RimeModule *module = rime_api->find_module("levers");
+  if (!module)
+    return NULL;
+  return (RimeLeversApi *)module->get_api();
+}
+
+// Return NULL when the custom settings cannot be prepared.
+RimeCustomSettings *c_custom_settings_init(RimeApi *rime_api,
+                                           const char *config_id) {
+  RimeLeversApi *levers = c_get_levers_api(rime_api);
+  if (!levers)
+    return NULL;
+  RimeCustomSettings *settings =
+      levers->custom_settings_init(config_id, "rimecmd");
+  // Load the existing patch, so that it is extended instead of replaced.
+  levers->load_settings(settings);
+  return settings;
+}
+
+void c_custom_settings_destroy(RimeApi *rime_api,
+                               RimeCustomSettings *settings) {
+  c_get_levers_api(rime_api)->custom_settings_destroy(settings);
+}
+
+Bool c_customize_bool(RimeApi *rime_api, RimeCustomSettings *settings,
+                      const char *key, Bool value) {
+  return c_get_levers_api(rime_api)->customize_bool(settings, key, value);
+}
+
+Bool c_customize_int(RimeApi *rime_api, RimeCustomSettings *settings,
+                     const char *key, int value) {
+  return c_get_levers_api(rime_api)->customize_int(settings, key, value);
+}
+
+Bool c_customize_double(RimeApi *rime_api, RimeCustomSettings *settings,
+                        const char *key, double value) {
+  return c_get_levers_api(rime_api)->customize_double(settings, key, value);
+}
+
+Bool c_customize_string(RimeApi *rime_api, RimeCustomSettings *settings,
+                        const char *key, const char *value) {
+  return c_get_levers_api(rime_api)->customize_string(settings, key, value);
+}
+
+Bool c_save_settings(RimeApi *rime_api, RimeCustomSettings *settings) {
+  return c_get_levers_api(rime_api)->save_settings(settings);
 }
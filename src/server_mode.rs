@@ -1,13 +1,16 @@
 use crate::json_request_processor::{JsonRequestProcessor, Outcome, Reply, Request};
 use crate::key_processor::KeyProcessor;
-use crate::rime_api::{RimeApi, RimeSession};
+use crate::logging::{self, log, LogLevel};
+use crate::rime_api::{RimeApi, RimeNotification, RimeSession};
 use crate::Config;
 use crate::Effect;
 use crate::{Error, Result};
 use signal_hook::consts::signal::{SIGINT, SIGTERM};
 use signal_hook::iterator::Signals;
-use std::fs::remove_file;
-use std::io::{Read, Write};
+use std::fs::{create_dir_all, remove_file, File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::net::Shutdown;
+use std::os::fd::AsRawFd;
 use std::os::unix::net::UnixListener;
 use std::os::unix::net::UnixStream;
 use std::sync::{
@@ -15,26 +18,57 @@ use std::sync::{
     Arc, Mutex,
 };
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long writing to a client can block, before the client is considered
+/// gone. A client that stops reading must not hold up the server.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct ServerMode {
     config: Config,
+    user_data_directory_lock: File,
     unix_listener: UnixListener,
+    ready_pipe: Option<File>,
 }
 
 impl ServerMode {
-    pub fn new(config: Config, unix_listener: UnixListener) -> Self {
+    /// * `user_data_directory_lock` - Returned by `lock_user_data_directory`,
+    ///   which is called before binding `unix_listener`, so that only the
+    ///   server using the user data directory touches the unix socket.
+    /// * `ready_pipe` - When the server is started by a client, the writing
+    ///   end of a pipe, which the client waits on until the server is ready.
+    pub fn new(
+        config: Config,
+        user_data_directory_lock: File,
+        unix_listener: UnixListener,
+        ready_pipe: Option<File>,
+    ) -> Self {
         Self {
             config,
+            user_data_directory_lock,
             unix_listener,
+            ready_pipe,
         }
     }
 
     pub fn main(self) -> Result<()> {
-        let (error_sender, error_receiver) = channel();
-        thread::spawn(move || loop {
-            let _error: Error = error_receiver.recv().unwrap();
-            eprintln!("{:?}", _error);
-            todo!("implement error logging");
+        logging::init(&self.config.log_file, self.config.log_level)?;
+        log!(
+            LogLevel::Info,
+            "server started on {}",
+            self.config.unix_socket.display()
+        );
+        // Released when the server stops, after the unix socket is removed.
+        let _user_data_directory_lock = self.user_data_directory_lock;
+        if let Some(mut ready_pipe) = self.ready_pipe {
+            // The pipe is closed when this is dropped.
+            ready_pipe.write_all(b"\n")?;
+        }
+        let (error_sender, error_receiver) = channel::<Error>();
+        thread::spawn(move || {
+            for error in error_receiver {
+                log!(LogLevel::Error, "{:?}", error);
+            }
         });
         let (stop_sender, stop_receiver) = channel();
         let stop_sender = Arc::new(Mutex::new(stop_sender));
@@ -63,48 +97,256 @@ impl ServerMode {
                 }
             }
         });
-        let rime_api = Arc::new(Mutex::new(RimeApi::new(
+        let mut rime_api = RimeApi::new(
             &self.config.user_data_directory,
             "/usr/share/rime-data",
             self.config.rime_log_level,
-        )));
+        );
+        let (notification_sender, notification_receiver) = channel();
+        rime_api.set_notification_handler(notification_sender);
+        let rime_api = Arc::new(Mutex::new(rime_api));
+        let client_writers: Arc<Mutex<Vec<Arc<ClientWriter>>>> = Arc::new(Mutex::new(vec![]));
+        let notification_client_writers = Arc::clone(&client_writers);
+        thread::spawn(move || {
+            for RimeNotification {
+                session_id,
+                message_type,
+                message_value,
+            } in notification_receiver
+            {
+                // Deployment concerns every session, while the other messages
+                // are about the session they come from.
+                let broadcast = message_type == "deploy";
+                let Ok(notification) = serde_json::to_string(&Reply {
+                    id: None,
+                    outcome: Outcome::Notification {
+                        message_type,
+                        message_value,
+                    },
+                }) else {
+                    continue;
+                };
+                log!(LogLevel::Trace, "notification {}", notification);
+                // Not holding the lock on the list while writing, so that
+                // clients can come and go meanwhile.
+                let client_writers: Vec<_> = notification_client_writers
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|client_writer| broadcast || client_writer.session_id == session_id)
+                    .map(Arc::clone)
+                    .collect();
+                for client_writer in client_writers {
+                    let mut client_stream = client_writer.stream.lock().unwrap();
+                    if let Err(err) = client_stream
+                        .write_all(notification.as_bytes())
+                        .and_then(|_| client_stream.flush())
+                    {
+                        log!(
+                            LogLevel::Warning,
+                            "connection {}: closing, cannot send notification: {}",
+                            client_writer.connection_id,
+                            err
+                        );
+                        // The session finds the connection closed and ends.
+                        let _ = client_stream.shutdown(Shutdown::Both);
+                    }
+                }
+            }
+        });
+        let client_count = Arc::new(Mutex::new(0));
+        let last_activity = Arc::new(Mutex::new(Instant::now()));
+        // Connections are accepted while holding the lock on `client_count`,
+        // so that while holding it, a connection is either counted, or still
+        // waiting to be accepted.
+        self.unix_listener.set_nonblocking(true)?;
+        if let Some(idle_timeout) = self.config.idle_timeout {
+            let idle_timeout = Duration::from_secs(idle_timeout);
+            let unix_listener = self.unix_listener.try_clone()?;
+            let stop_sender = Arc::clone(&stop_sender);
+            let client_count = Arc::clone(&client_count);
+            let last_activity = Arc::clone(&last_activity);
+            thread::spawn(move || loop {
+                let locked_client_count = client_count.lock().unwrap();
+                let idle_for = last_activity.lock().unwrap().elapsed();
+                if *locked_client_count == 0
+                    && idle_for >= idle_timeout
+                    && !matches!(wait_for_connection(&unix_listener, 0), Ok(true))
+                {
+                    log!(
+                        LogLevel::Info,
+                        "stopping after being idle for {} seconds",
+                        idle_for.as_secs()
+                    );
+                    stop_sender.lock().unwrap().send(()).unwrap();
+                    // Nothing is accepted anymore, until the server exits.
+                    loop {
+                        thread::park();
+                    }
+                }
+                drop(locked_client_count);
+                // When there are clients, check again a while later.
+                thread::sleep(
+                    idle_timeout
+                        .saturating_sub(idle_for)
+                        .max(Duration::from_secs(1)),
+                );
+            });
+        }
         thread::spawn(move || {
-            let client_count = Arc::new(Mutex::new(0));
-            for stream in self.unix_listener.incoming() {
-                *client_count.lock().unwrap() += 1;
-                let stream = match stream {
-                    Ok(stream) => stream,
+            for connection_id in 0.. {
+                if let Err(err) = wait_for_connection(&self.unix_listener, -1) {
+                    error_sender.lock().unwrap().send(err).unwrap();
+                    break;
+                }
+                let mut locked_client_count = client_count.lock().unwrap();
+                let stream = match self.unix_listener.accept() {
+                    Ok((stream, _)) => stream,
+                    // Taken by another process, or found to be gone.
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => continue,
                     Err(err) => {
                         error_sender.lock().unwrap().send(err.into()).unwrap();
                         break;
                     }
                 };
-                let error_sender = Arc::clone(&error_sender);
+                let client_stream_writer = match stream
+                    .set_nonblocking(false)
+                    .and_then(|_| stream.try_clone())
+                    .and_then(|writer| {
+                        writer
+                            .set_write_timeout(Some(CLIENT_WRITE_TIMEOUT))
+                            .map(|_| writer)
+                    }) {
+                    Ok(writer) => Arc::new(Mutex::new(writer)),
+                    Err(err) => {
+                        error_sender.lock().unwrap().send(err.into()).unwrap();
+                        continue;
+                    }
+                };
+                *locked_client_count += 1;
+                drop(locked_client_count);
+                *last_activity.lock().unwrap() = Instant::now();
+                let client_writers = Arc::clone(&client_writers);
+                let last_activity = Arc::clone(&last_activity);
                 let stop_sender = Arc::clone(&stop_sender);
                 let client_count = Arc::clone(&client_count);
                 let rime_api = Arc::clone(&rime_api);
                 thread::spawn(move || {
+                    log!(LogLevel::Info, "connection {}: opened", connection_id);
+                    let rime_session = RimeSession::new(rime_api);
+                    let client_writer = Arc::new(ClientWriter {
+                        connection_id,
+                        session_id: rime_session.session_id(),
+                        stream: Arc::clone(&client_stream_writer),
+                    });
+                    client_writers
+                        .lock()
+                        .unwrap()
+                        .push(Arc::clone(&client_writer));
                     Session {
+                        connection_id,
                         client_stream: stream,
+                        client_stream_writer,
                         client_count: Arc::clone(&client_count),
-                        rime_session: RimeSession::new(rime_api),
+                        last_activity: Arc::clone(&last_activity),
+                        rime_session,
                         stop_sender,
                     }
                     .run()
-                    .unwrap_or_else(|err| error_sender.lock().unwrap().send(err).unwrap());
-                    *client_count.lock().unwrap() -= 1;
+                    .unwrap_or_else(|err| {
+                        log!(LogLevel::Error, "connection {}: {:?}", connection_id, err)
+                    });
+                    client_writers
+                        .lock()
+                        .unwrap()
+                        .retain(|writer| !Arc::ptr_eq(writer, &client_writer));
+                    // Both at once, so that the server is not seen idle
+                    // for the time before the client connected.
+                    let mut locked_client_count = client_count.lock().unwrap();
+                    *locked_client_count -= 1;
+                    *last_activity.lock().unwrap() = Instant::now();
+                    drop(locked_client_count);
+                    log!(LogLevel::Info, "connection {}: closed", connection_id);
                 });
             }
         });
         stop_receiver.recv().unwrap();
+        log!(LogLevel::Info, "server stopped");
         remove_file(&self.config.unix_socket)?;
         Ok(())
     }
 }
 
+/// Wait until a client connects to `unix_listener`, for at most `timeout`
+/// milliseconds, or forever if it is negative. Whether a client is waiting
+/// to be accepted.
+fn wait_for_connection(unix_listener: &UnixListener, timeout: libc::c_int) -> Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd: unix_listener.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    loop {
+        match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+            -1 => {
+                let error = std::io::Error::last_os_error();
+                if error.kind() != ErrorKind::Interrupted {
+                    return Err(error.into());
+                }
+            }
+            count => return Ok(count > 0),
+        }
+    }
+}
+
+/// Take an exclusive lock on the lock file in the user data directory, so
+/// that only one server uses the user data directory at a time. The lock is
+/// held as long as the returned file is open. The lock file records the unix
+/// socket and the PID of the server holding the lock.
+pub fn lock_user_data_directory(config: &Config) -> Result<File> {
+    create_dir_all(&config.user_data_directory)?;
+    // Not truncated here, because the content belongs to the owner of the
+    // lock until the lock is taken.
+    let mut lock_file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(config.user_data_directory.join("rimecmd.lock"))?;
+    if -1 == unsafe { libc::flock(lock_file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } {
+        let error = std::io::Error::last_os_error();
+        if error.kind() != ErrorKind::WouldBlock {
+            return Err(error.into());
+        }
+        let mut content = String::new();
+        lock_file.read_to_string(&mut content)?;
+        return Err(Error::UserDataDirectoryInUse(
+            content.lines().next().unwrap_or_default().into(),
+        ));
+    }
+    lock_file.set_len(0)?;
+    writeln!(lock_file, "{}", config.unix_socket.display())?;
+    writeln!(lock_file, "{}", std::process::id())?;
+    lock_file.flush()?;
+    Ok(lock_file)
+}
+
+/// A client as the thread that sends notifications sees it.
+struct ClientWriter {
+    connection_id: usize,
+    /// The Rime session serving the client.
+    session_id: usize,
+    stream: Arc<Mutex<UnixStream>>,
+}
+
 struct Session {
+    connection_id: usize,
     client_stream: UnixStream,
+    // The same socket as `client_stream`, shared with the thread that sends
+    // notifications, so that replies and notifications do not interleave.
+    client_stream_writer: Arc<Mutex<UnixStream>>,
     client_count: Arc<Mutex<usize>>,
+    last_activity: Arc<Mutex<Instant>>,
     rime_session: RimeSession,
     stop_sender: Arc<Mutex<Sender<()>>>,
 }
@@ -133,10 +375,13 @@ impl Session {
 
     pub fn run(self) -> Result<()> {
         let Self {
+            connection_id,
             client_count,
+            last_activity,
             rime_session,
             stop_sender,
             mut client_stream,
+            client_stream_writer,
         } = self;
         let json_request_processor = JsonRequestProcessor {
             rime_session: &rime_session,
@@ -144,6 +389,15 @@ impl Session {
         };
         loop {
             let request = Self::read_request(&mut client_stream);
+            *last_activity.lock().unwrap() = Instant::now();
+            if let Ok(ref request) = request {
+                log!(
+                    LogLevel::Trace,
+                    "connection {}: request {}",
+                    connection_id,
+                    serde_json::to_string(request)?
+                );
+            }
             let reply = match request {
                 Ok(request) => json_request_processor.process_request(request),
                 Err(err) => match err.try_into() {
@@ -164,8 +418,7 @@ impl Session {
                     outcome: Outcome::Effect(Effect::StopClient),
                     ..
                 } => {
-                    client_stream.write(serde_json::to_string(&reply)?.as_bytes())?;
-                    client_stream.flush()?;
+                    Self::write_reply(connection_id, &client_stream_writer, &reply)?;
                     return Self::check_client_stream_closed(&mut client_stream);
                 }
                 Reply {
@@ -177,30 +430,46 @@ impl Session {
                         // One string reference is the stop sender here, the other is the one in the
                         // thread that starts threads on `incoming` streams. In such a case, this
                         // is the only thread that is serving a client.
-                        client_stream.write(serde_json::to_string(&reply)?.as_bytes())?;
-                        client_stream.flush()?;
+                        Self::write_reply(connection_id, &client_stream_writer, &reply)?;
                         let result = Self::check_client_stream_closed(&mut client_stream);
                         stop_sender.lock().unwrap().send(()).unwrap();
                         return result;
                     } else {
-                        client_stream.write(
-                            serde_json::to_string(&Reply {
+                        Self::write_reply(
+                            connection_id,
+                            &client_stream_writer,
+                            &Reply {
                                 id: id.clone(),
                                 outcome: Error::MoreThanOneClient.try_into().unwrap(),
-                            })?
-                            .as_bytes(),
+                            },
                         )?;
-                        client_stream.flush()?;
                     }
                 }
                 _ => {
-                    client_stream.write(serde_json::to_string(&reply)?.as_bytes())?;
-                    client_stream.flush()?;
+                    Self::write_reply(connection_id, &client_stream_writer, &reply)?;
                 }
             }
         }
     }
 
+    fn write_reply(
+        connection_id: usize,
+        client_stream_writer: &Mutex<UnixStream>,
+        reply: &Reply,
+    ) -> Result<()> {
+        let reply = serde_json::to_string(reply)?;
+        log!(
+            LogLevel::Trace,
+            "connection {}: reply {}",
+            connection_id,
+            reply
+        );
+        let mut client_stream = client_stream_writer.lock().unwrap();
+        client_stream.write_all(reply.as_bytes())?;
+        client_stream.flush()?;
+        Ok(())
+    }
+
     fn check_client_stream_closed(client_stream: &mut UnixStream) -> Result<()> {
         match Self::read_request(client_stream) {
             Err(Error::OneOfMultipleInputClosed) => Ok(()),
@@ -106,6 +106,10 @@ impl ParserStateImpl {
                         + char::from_u32(byte.into()).unwrap().to_digit(10).unwrap() as usize,
                 )
             }
+            // Shift-Delete
+            ParserStateImpl::Csi2NumParam(3, 2) if byte == 0x7e => {
+                ParserStateImpl::Completed(Input::ShiftDelete)
+            }
             ParserStateImpl::Csi2NumParam(param1, param2) if byte == b'R' => {
                 ParserStateImpl::Completed(Input::CursorPositionReport {
                     row: param1.try_into().unwrap(),
@@ -259,6 +263,21 @@ mod test {
         }
     }
 
+    #[test]
+    fn shift_delete() {
+        if let ParserStateImpl::Completed(Input::ShiftDelete) = ParserStateImpl::Start
+            .consume_byte(0x1b)
+            .consume_byte(0x5b)
+            .consume_byte(0x33)
+            .consume_byte(0x3b)
+            .consume_byte(0x32)
+            .consume_byte(0x7e)
+        {
+        } else {
+            panic!();
+        }
+    }
+
     #[test]
     fn page_up() {
         if let ParserStateImpl::Completed(Input::PageUp) = ParserStateImpl::Start
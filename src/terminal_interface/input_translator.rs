@@ -72,6 +72,13 @@ impl InputTranslator {
                 keycode: *self.rime_key_name_to_key_code_map.get("Delete").unwrap(),
                 mask: 0,
             }),
+            Input::ShiftDelete => Some(RimeKey {
+                keycode: *self.rime_key_name_to_key_code_map.get("Delete").unwrap(),
+                mask: *self
+                    .rime_modifier_name_to_modifer_mask
+                    .get("Shift")
+                    .unwrap(),
+            }),
             Input::Insert => Some(RimeKey {
                 keycode: *self.rime_key_name_to_key_code_map.get("Insert").unwrap(),
                 mask: 0,
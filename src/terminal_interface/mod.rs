@@ -40,6 +40,7 @@ pub enum Input {
     KeypadHome,
     Insert,
     Delete,
+    ShiftDelete,
     KeypadEnd,
     PageUp,
     PageDown,
@@ -63,6 +64,9 @@ pub struct TerminalInterface {
     original_mode: Option<libc::termios>,
     input_translator: input_translator::InputTranslator,
     input_buffer: VecDeque<Input>,
+    /// The highlighted candidate's index on the menu drawn last time, if
+    /// the menu has any candidate.
+    highlighted_candidate_index: Option<usize>,
 }
 
 type Result<T> = std::result::Result<T, crate::Error>;
@@ -96,6 +100,7 @@ impl TerminalInterface {
                 })?,
             original_mode: None,
             input_translator: input_translator::InputTranslator::new(),
+            highlighted_candidate_index: None,
         })
     }
 
@@ -136,13 +141,14 @@ impl TerminalInterface {
         let mut height = 0;
         for (index, candidate) in menu.candidates.iter().enumerate() {
             self.tty_file.write(b"\r\n")?;
+            let label = &menu.labels[index];
             if index == menu.highlighted_candidate_index {
-                // The escape code here gives the index inverted color,
+                // The escape code here gives the label inverted color,
                 self.tty_file
-                    .write(format!("\x1b[7m{}.\x1b[0m {}", index + 1, candidate.text).as_bytes())?;
+                    .write(format!("\x1b[7m{}.\x1b[0m {}", label, candidate.text).as_bytes())?;
             } else {
                 self.tty_file
-                    .write(format!("{}. {}", index + 1, candidate.text).as_bytes())?;
+                    .write(format!("{}. {}", label, candidate.text).as_bytes())?;
             }
             if let Some(comment) = candidate.comment.as_ref() {
                 self.set_character_attribute(CharacterAttribute::Faint)?;
@@ -223,6 +229,14 @@ impl TerminalInterface {
         };
         match input {
             Input::Etx | Input::Eot => Ok(Call::StopClient),
+            // Without a menu there is nothing to delete, so Shift-Delete is then sent to
+            // Rime as a normal key.
+            Input::ShiftDelete if self.highlighted_candidate_index.is_some() => {
+                Ok(Call::DeleteCandidate {
+                    index: self.highlighted_candidate_index.unwrap(),
+                    on_current_page: true,
+                })
+            }
             input => {
                 let Some(input_translator::RimeKey { keycode, mask }) =
                     self.input_translator.translate_input(input)
@@ -235,6 +249,8 @@ impl TerminalInterface {
     }
 
     pub fn update_ui(&mut self, composition: &RimeComposition, menu: &RimeMenu) -> Result<()> {
+        self.highlighted_candidate_index =
+            (!menu.candidates.is_empty()).then_some(menu.highlighted_candidate_index);
         self.carriage_return()?;
         let final_cursor_col = self.draw_composition(composition)?;
         let final_cursor_row = self.draw_menu(menu)?;
@@ -244,6 +260,7 @@ impl TerminalInterface {
     }
 
     pub fn setup_ui(&mut self) -> Result<()> {
+        self.highlighted_candidate_index = None;
         self.carriage_return()?;
         self.tty_file.write(b"> ")?;
         self.erase_after()?;
@@ -88,48 +88,45 @@ impl TerminalJsonMode {
                 Input::ServerReply(ReplyState::Complete(reply)) => reply,
                 Input::ServerReply(ReplyState::Incomplete) => continue,
             };
-            match reply {
-                Reply {
-                    outcome: Outcome::Effect(Effect::CommitString(_) | Effect::RawKeyEvent { .. }),
-                    ..
-                } => {
-                    if !continue_mode {
-                        stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
-                        stdout().flush()?;
-                        break;
-                    } else {
-                        terminal_interface.borrow_mut().remove_ui()?;
-                        stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
-                        stdout().flush()?;
-                        terminal_interface.borrow_mut().setup_ui()?;
-                    }
-                }
-                Reply {
-                    outcome:
-                        Outcome::Effect(Effect::UpdateUi {
-                            ref menu,
-                            ref composition,
-                        }),
-                    ..
-                } => {
+            if let Reply {
+                outcome: Outcome::Effect(Effect::StopClient | Effect::StopServer),
+                ..
+            } = reply
+            {
+                stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
+                stdout().flush()?;
+                break;
+            }
+            let effects = match reply.outcome {
+                Outcome::Effect(ref effect) => std::slice::from_ref(effect),
+                Outcome::Effects(ref effects) => effects.as_slice(),
+                _ => &[],
+            };
+            if effects.iter().any(|effect| {
+                matches!(effect, Effect::CommitString(_) | Effect::RawKeyEvent { .. })
+            }) {
+                if !continue_mode {
                     stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
                     stdout().flush()?;
+                    break;
+                }
+                terminal_interface.borrow_mut().remove_ui()?;
+                stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
+                stdout().flush()?;
+                terminal_interface.borrow_mut().setup_ui()?;
+            } else {
+                stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
+                stdout().flush()?;
+            }
+            for effect in effects {
+                if let Effect::UpdateUi {
+                    menu, composition, ..
+                } = effect
+                {
                     terminal_interface
                         .borrow_mut()
                         .update_ui(composition, menu)?;
                 }
-                Reply {
-                    outcome: Outcome::Effect(Effect::StopClient | Effect::StopServer),
-                    ..
-                } => {
-                    stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
-                    stdout().flush()?;
-                    break;
-                }
-                reply => {
-                    stdout().write(&serde_json::to_string(&reply).unwrap().as_bytes())?;
-                    stdout().flush()?;
-                }
             }
         }
         drop(poll_data);
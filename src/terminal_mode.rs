@@ -35,10 +35,12 @@ impl TerminalMode {
         continue_mode: bool,
     ) -> Result<(), Error> {
         terminal_interface.open()?;
-        loop {
+        'main: loop {
             let call = terminal_interface.next_call()?;
             let reply = match call {
-                call @ (Call::ProcessKey { .. } | Call::StopClient) => {
+                call @ (Call::ProcessKey { .. }
+                | Call::DeleteCandidate { .. }
+                | Call::StopClient) => {
                     client.send_bytes(
                         serde_json::to_string(&Request {
                             id: Uuid::new_v4().into(),
@@ -46,72 +48,74 @@ impl TerminalMode {
                         })?
                         .as_bytes(),
                     )?;
-                    match client.read_data()? {
-                        ReplyState::Complete(reply) => reply,
-                        ReplyState::Incomplete => continue,
+                    // Notifications are not shown in this mode.
+                    loop {
+                        match client.read_data()? {
+                            ReplyState::Complete(Reply {
+                                outcome: Outcome::Notification { .. },
+                                ..
+                            })
+                            | ReplyState::Incomplete => continue,
+                            ReplyState::Complete(reply) => break reply,
+                        }
                     }
                 }
                 _ => unreachable!(),
             };
-            match reply {
-                Reply {
-                    outcome: Outcome::Effect(Effect::CommitString(commit_string)),
-                    ..
-                } => {
-                    terminal_interface.remove_ui()?;
-                    stdout().write(commit_string.as_bytes())?;
-                    stdout().write(b"\n")?;
-                    stdout().flush()?;
-                    if continue_mode {
-                        terminal_interface.setup_ui()?;
-                    } else {
-                        break;
-                    }
-                }
-                Reply {
-                    outcome:
-                        Outcome::Effect(Effect::RawKeyEvent {
-                            keycode,
-                            accompanying_commit_string,
-                            ..
-                        }),
-                    ..
-                } => {
-                    // Rime supports many more keys, but for now only support ASCII here.
-                    let Some((c, true)) = char::from_u32(keycode as u32).map(|c| (c, c.is_ascii()))
-                    else {
-                        continue;
-                    };
-                    terminal_interface.remove_ui()?;
-                    if let Some(commit_string) = accompanying_commit_string {
+            let effects = match reply.outcome {
+                Outcome::Effect(effect) => vec![effect],
+                Outcome::Effects(effects) => effects,
+                _ => continue,
+            };
+            for effect in effects {
+                match effect {
+                    Effect::CommitString(commit_string) => {
+                        terminal_interface.remove_ui()?;
                         stdout().write(commit_string.as_bytes())?;
+                        stdout().write(b"\n")?;
+                        stdout().flush()?;
+                        if continue_mode {
+                            terminal_interface.setup_ui()?;
+                        } else {
+                            break 'main;
+                        }
                     }
-                    stdout().write(c.to_string().as_bytes())?;
-                    stdout().write(b"\n")?;
-                    stdout().flush()?;
-                    if continue_mode {
-                        terminal_interface.setup_ui()?;
-                    } else {
-                        break;
+                    Effect::RawKeyEvent {
+                        keycode,
+                        accompanying_commit_string,
+                        ..
+                    } => {
+                        // Rime supports many more keys, but for now only support ASCII here.
+                        let Some((c, true)) =
+                            char::from_u32(keycode as u32).map(|c| (c, c.is_ascii()))
+                        else {
+                            continue;
+                        };
+                        terminal_interface.remove_ui()?;
+                        if let Some(commit_string) = accompanying_commit_string {
+                            stdout().write(commit_string.as_bytes())?;
+                        }
+                        stdout().write(c.to_string().as_bytes())?;
+                        stdout().write(b"\n")?;
+                        stdout().flush()?;
+                        if continue_mode {
+                            terminal_interface.setup_ui()?;
+                        } else {
+                            break 'main;
+                        }
                     }
+                    Effect::UpdateUi {
+                        ref menu,
+                        ref composition,
+                        ..
+                    } => {
+                        terminal_interface.update_ui(composition, menu)?;
+                    }
+                    Effect::StopClient => {
+                        break 'main;
+                    }
+                    _ => (),
                 }
-                Reply {
-                    outcome:
-                        Outcome::Effect(Effect::UpdateUi {
-                            ref menu,
-                            ref composition,
-                        }),
-                    ..
-                } => {
-                    terminal_interface.update_ui(composition, menu)?;
-                }
-                Reply {
-                    outcome: Outcome::Effect(Effect::StopClient),
-                    ..
-                } => {
-                    break;
-                }
-                _ => (),
             }
         }
         client.shutdown()?;